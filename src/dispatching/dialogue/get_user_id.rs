@@ -0,0 +1,25 @@
+use crate::types::{CallbackQuery, Message, Update, UserId};
+
+/// Something that may has a user ID.
+pub trait GetUserId {
+    #[must_use]
+    fn user_id(&self) -> Option<UserId>;
+}
+
+impl GetUserId for Message {
+    fn user_id(&self) -> Option<UserId> {
+        self.from().map(|user| user.id)
+    }
+}
+
+impl GetUserId for CallbackQuery {
+    fn user_id(&self) -> Option<UserId> {
+        Some(self.from.id)
+    }
+}
+
+impl GetUserId for Update {
+    fn user_id(&self) -> Option<UserId> {
+        self.user().map(|user| user.id)
+    }
+}
@@ -4,10 +4,10 @@
 //! wrapper over [`Storage`] and a chat ID. All it does is provides convenient
 //! method for manipulating the dialogue state. [`Storage`] is where all
 //! dialogue states are stored; it can be either [`InMemStorage`], which is a
-//! simple hash map, or database wrappers such as [`SqliteStorage`]. In the
-//! latter case, your dialogues are _persistent_, meaning that you can safely
-//! restart your bot and all dialogues will remain in the database -- this is a
-//! preferred method for production bots.
+//! simple hash map, or database wrappers such as [`SqliteStorage`] and
+//! [`PostgresStorage`]. In the latter case, your dialogues are _persistent_,
+//! meaning that you can safely restart your bot and all dialogues will remain
+//! in the database -- this is a preferred method for production bots.
 //!
 //! [`examples/dialogue.rs`] clearly demonstrates the typical usage of
 //! dialogues. Your dialogue state can be represented as an enumeration:
@@ -83,66 +83,81 @@ pub use crate::dispatching::dialogue::{RedisStorage, RedisStorageError};
 #[cfg(feature = "sqlite-storage")]
 pub use crate::dispatching::dialogue::{SqliteStorage, SqliteStorageError};
 
+#[cfg(feature = "postgres-storage")]
+pub use crate::dispatching::dialogue::{
+    PostgresStorage, PostgresStorageBuilder, PostgresStorageError,
+};
+
 use dptree::{prelude::DependencyMap, Handler};
 pub use get_chat_id::GetChatId;
+pub use get_user_id::GetUserId;
 pub use storage::*;
-use teloxide_core::types::ChatId;
+use teloxide_core::types::{ChatId, UserId};
 
 use std::{fmt::Debug, marker::PhantomData, sync::Arc};
 
 use super::DpHandlerDescription;
 
 mod get_chat_id;
+mod get_user_id;
 mod storage;
 
 /// A handle for controlling dialogue state.
+///
+/// A dialogue is indexed in its storage by a key `K`, which is the ID of the
+/// chat by default (see [`enter_with_key`] for other keys).
 #[derive(Debug)]
-pub struct Dialogue<D, S>
+pub struct Dialogue<D, S, K = ChatId>
 where
     S: ?Sized,
 {
     storage: Arc<S>,
-    chat_id: ChatId,
+    key: K,
     _phantom: PhantomData<D>,
 }
 
 // `#[derive]` requires generics to implement `Clone`, but `S` is wrapped around
 // `Arc`, and `D` is wrapped around PhantomData.
-impl<D, S> Clone for Dialogue<D, S>
+impl<D, S, K> Clone for Dialogue<D, S, K>
 where
     S: ?Sized,
+    K: Clone,
 {
     fn clone(&self) -> Self {
-        Dialogue { storage: self.storage.clone(), chat_id: self.chat_id, _phantom: PhantomData }
+        Dialogue { storage: self.storage.clone(), key: self.key.clone(), _phantom: PhantomData }
     }
 }
 
-impl<D, S> Dialogue<D, S>
+impl<D, S, K> Dialogue<D, S, K>
 where
     D: Send + 'static,
-    S: Storage<D> + ?Sized,
+    S: Storage<D, K> + ?Sized,
+    K: Clone,
 {
     /// Constructs a new dialogue with `storage` (where dialogues are stored)
-    /// and `chat_id` of a current dialogue.
+    /// and `key` of a current dialogue, e.g. a chat ID.
     #[must_use]
-    pub fn new(storage: Arc<S>, chat_id: ChatId) -> Self {
-        Self { storage, chat_id, _phantom: PhantomData }
+    pub fn new(storage: Arc<S>, key: K) -> Self {
+        Self { storage, key, _phantom: PhantomData }
     }
 
-    /// Returns a chat ID associated with this dialogue.
+    /// Returns a key associated with this dialogue.
     #[must_use]
-    pub fn chat_id(&self) -> ChatId {
-        self.chat_id
+    pub fn key(&self) -> K {
+        self.key.clone()
     }
 
     /// Retrieves the current state of the dialogue or `None` if there is no
     /// dialogue.
     pub async fn get(&self) -> Result<Option<D>, S::Error> {
-        self.storage.clone().get_dialogue(self.chat_id).await
+        self.storage.clone().get_dialogue(self.key()).await
     }
 
     /// Like [`Dialogue::get`] but returns a default value if there is no
     /// dialogue.
+    ///
+    /// The default value is also written to the storage, so the next call to
+    /// [`Dialogue::get`] returns `Some(_)`.
     pub async fn get_or_default(&self) -> Result<D, S::Error>
     where
         D: Default,
@@ -150,7 +165,7 @@ where
         match self.get().await? {
             Some(d) => Ok(d),
             None => {
-                self.storage.clone().update_dialogue(self.chat_id, D::default()).await?;
+                self.storage.clone().update_dialogue(self.key(), D::default()).await?;
                 Ok(D::default())
             }
         }
@@ -165,7 +180,7 @@ where
         D: From<State>,
     {
         let new_dialogue = state.into();
-        self.storage.clone().update_dialogue(self.chat_id, new_dialogue).await?;
+        self.storage.clone().update_dialogue(self.key(), new_dialogue).await?;
         Ok(())
     }
 
@@ -179,7 +194,18 @@ where
 
     /// Removes the dialogue from the storage provided to [`Dialogue::new`].
     pub async fn exit(&self) -> Result<(), S::Error> {
-        self.storage.clone().remove_dialogue(self.chat_id).await
+        self.storage.clone().remove_dialogue(self.key()).await
+    }
+}
+
+impl<D, S> Dialogue<D, S>
+where
+    S: ?Sized,
+{
+    /// Returns a chat ID associated with this dialogue.
+    #[must_use]
+    pub fn chat_id(&self) -> ChatId {
+        self.key
     }
 }
 
@@ -202,13 +228,57 @@ where
     D: Default + Send + Sync + 'static,
     Upd: GetChatId + Clone + Send + Sync + 'static,
     Output: Send + Sync + 'static,
+{
+    enter_with_key::<Upd, S, D, Output, ChatId, _>(Upd::chat_id)
+}
+
+/// Enters a dialogue context, identifying the dialogue of an update by `key`.
+///
+/// A call to this function is the same as
+/// `dptree::entry().enter_dialogue_with_key(key)`.
+///
+/// `key` returns a key of type `K`, by which the dialogue is indexed in the
+/// storage `S`, e.g. [`user_key`] or [`chat_user_key`]. The dialogue is then
+/// available as [`Dialogue<D, S, K>`].
+///
+/// [`Dispatcher`] handles updates with the same [distribution key]
+/// sequentially, which by default is the chat of an update. A dialogue state
+/// is read before a handler runs and written after, so if `key` can map
+/// updates from different chats to the same dialogue (as [`user_key`] does),
+/// configure [`DispatcherBuilder::distribution_function`] to distribute
+/// updates by the same key, or concurrent updates may overwrite each other's
+/// state. Keys that include the chat, such as [`chat_user_key`], are fine with
+/// the default distribution.
+///
+/// See [`HandlerExt::enter_dialogue_with_key`].
+///
+/// ## Dependency requirements
+///
+///  - `Arc<S>`
+///  - `Upd`
+///
+/// [`HandlerExt::enter_dialogue_with_key`]: super::HandlerExt::enter_dialogue_with_key
+/// [`Dispatcher`]: super::Dispatcher
+/// [distribution key]: super::DefaultKey
+/// [`DispatcherBuilder::distribution_function`]: super::DispatcherBuilder::distribution_function
+pub fn enter_with_key<Upd, S, D, Output, K, F>(
+    key: F,
+) -> Handler<'static, DependencyMap, Output, DpHandlerDescription>
+where
+    S: Storage<D, K> + ?Sized + Send + Sync + 'static,
+    <S as Storage<D, K>>::Error: Debug + Send,
+    D: Default + Send + Sync + 'static,
+    Upd: Clone + Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+    K: Clone + Send + Sync + 'static,
+    F: Fn(&Upd) -> Option<K> + Send + Sync + 'static,
 {
     dptree::entry()
-        .chain(dptree::filter_map(|storage: Arc<S>, upd: Upd| {
-            let chat_id = upd.chat_id()?;
-            Some(Dialogue::new(storage, chat_id))
+        .chain(dptree::filter_map(move |storage: Arc<S>, upd: Upd| {
+            let key = key(&upd)?;
+            Some(Dialogue::new(storage, key))
         }))
-        .chain(dptree::filter_map_async(|dialogue: Dialogue<D, S>| async move {
+        .chain(dptree::filter_map_async(|dialogue: Dialogue<D, S, K>| async move {
             match dialogue.get_or_default().await {
                 Ok(dialogue) => Some(dialogue),
                 Err(err) => {
@@ -218,3 +288,179 @@ where
             }
         }))
 }
+
+/// A dialogue key for [`enter_with_key`], which identifies a dialogue by the
+/// user who has sent an update, regardless of the chat.
+///
+/// A user has the same dialogue in all chats, including the private chat with
+/// the bot. Since updates from different chats are handled concurrently by
+/// default, distribute them by user with
+/// [`DispatcherBuilder::distribution_function`], as shown below. Use
+/// [`chat_user_key`] to have a separate dialogue for each user in each chat.
+///
+/// ## Example
+///
+/// ```
+/// use teloxide::{
+///     dispatching::dialogue::{self, InMemStorage},
+///     prelude::*,
+///     types::UserId,
+/// };
+///
+/// #[derive(Clone)]
+/// enum State {
+///     Start,
+///     ReceiveFullName,
+/// }
+///
+/// impl Default for State {
+///     fn default() -> Self {
+///         Self::Start
+///     }
+/// }
+///
+/// type MyStorage = InMemStorage<State, UserId>;
+/// type MyDialogue = Dialogue<State, MyStorage, UserId>;
+///
+/// # async fn run() {
+/// let bot = Bot::new("TOKEN");
+/// let handler = Update::filter_message()
+///     .enter_dialogue_with_key::<Message, MyStorage, State, _, _>(dialogue::user_key)
+///     .endpoint(|dialogue: MyDialogue| async move {
+///         // Only the dialogue of the user who has sent the message is updated.
+///         dialogue.update(State::ReceiveFullName).await
+///     });
+///
+/// Dispatcher::builder(bot, handler)
+///     .dependencies(dptree::deps![MyStorage::new()])
+///     // Handle updates of the same user sequentially, in all chats.
+///     .distribution_function(|upd| upd.user().map(|user| user.id))
+///     .build()
+///     .dispatch()
+///     .await;
+/// # }
+/// ```
+///
+/// [`DispatcherBuilder::distribution_function`]: super::DispatcherBuilder::distribution_function
+#[must_use]
+pub fn user_key<Upd>(upd: &Upd) -> Option<UserId>
+where
+    Upd: GetUserId,
+{
+    upd.user_id()
+}
+
+/// A dialogue key for [`enter_with_key`], which identifies a dialogue by the
+/// chat of an update and the user who has sent it.
+///
+/// Use it in group chats, so that several users can go through the same
+/// dialogue simultaneously without interfering with each other, while the
+/// dialogue of a user in one chat is independent of their dialogues in other
+/// chats. Unlike [`user_key`], it works with the default distribution of
+/// updates by [`Dispatcher`].
+///
+/// ## Example
+///
+/// ```
+/// use teloxide::{
+///     dispatching::dialogue::{self, InMemStorage},
+///     prelude::*,
+///     types::{ChatId, UserId},
+/// };
+///
+/// type MyStorage = InMemStorage<i32, (ChatId, UserId)>;
+/// type MyDialogue = Dialogue<i32, MyStorage, (ChatId, UserId)>;
+///
+/// let handler = Update::filter_message()
+///     .enter_dialogue_with_key::<Message, MyStorage, i32, _, _>(dialogue::chat_user_key)
+///     .endpoint(
+///         |dialogue: MyDialogue, count: i32| async move { dialogue.update(count + 1).await },
+///     );
+/// # let _ = handler;
+/// ```
+///
+/// [`Dispatcher`]: super::Dispatcher
+#[must_use]
+pub fn chat_user_key<Upd>(upd: &Upd) -> Option<(ChatId, UserId)>
+where
+    Upd: GetChatId + GetUserId,
+{
+    Some((upd.chat_id()?, upd.user_id()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::ControlFlow;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_get_or_default() {
+        let storage = InMemStorage::<i32>::new();
+        let dialogue = Dialogue::new(storage, ChatId(123));
+
+        assert_eq!(dialogue.get().await.unwrap(), None);
+        assert_eq!(dialogue.get_or_default().await.unwrap(), 0);
+        assert_eq!(dialogue.get().await.unwrap(), Some(0));
+
+        dialogue.update(42_i32).await.unwrap();
+        assert_eq!(dialogue.get_or_default().await.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn test_enter_with_key() {
+        // An update in one of two chats from one of two users.
+        #[derive(Clone)]
+        struct Upd {
+            chat: i64,
+            user: u64,
+        }
+
+        impl GetChatId for Upd {
+            fn chat_id(&self) -> Option<ChatId> {
+                Some(ChatId(self.chat))
+            }
+        }
+
+        impl GetUserId for Upd {
+            fn user_id(&self) -> Option<UserId> {
+                Some(UserId(self.user))
+            }
+        }
+
+        type Key = (ChatId, UserId);
+        type S = InMemStorage<i32, Key>;
+
+        let storage = S::new();
+        let handler = enter_with_key::<Upd, S, i32, _, Key, _>(chat_user_key).endpoint(
+            |dialogue: Dialogue<i32, S, Key>, state: i32| async move {
+                dialogue.update(state + 1).await.unwrap();
+                state
+            },
+        );
+
+        let dispatch =
+            |chat, user| handler.dispatch(dptree::deps![Arc::clone(&storage), Upd { chat, user }]);
+        assert!(matches!(dispatch(1, 1).await, ControlFlow::Break(0)));
+        assert!(matches!(dispatch(1, 1).await, ControlFlow::Break(1)));
+        assert!(matches!(dispatch(1, 2).await, ControlFlow::Break(0)));
+        assert!(matches!(dispatch(2, 1).await, ControlFlow::Break(0)));
+
+        // A user has the same dialogue in all chats.
+        type ByUser = InMemStorage<i32, UserId>;
+
+        let by_user = ByUser::new();
+        let handler = enter_with_key::<Upd, ByUser, i32, _, UserId, _>(user_key).endpoint(
+            |dialogue: Dialogue<i32, ByUser, UserId>, state: i32| async move {
+                dialogue.update(state + 1).await.unwrap();
+                state
+            },
+        );
+
+        let dispatch =
+            |chat, user| handler.dispatch(dptree::deps![Arc::clone(&by_user), Upd { chat, user }]);
+        assert!(matches!(dispatch(1, 1).await, ControlFlow::Break(0)));
+        assert!(matches!(dispatch(2, 1).await, ControlFlow::Break(1)));
+        assert!(matches!(dispatch(2, 2).await, ControlFlow::Break(0)));
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use futures::future::BoxFuture;
+use teloxide_core::types::ChatId;
+use tokio::sync::Mutex;
+
+use crate::dispatching::dialogue::{ListableStorage, Storage};
+
+/// A dialogue storage wrapper which caches recently used dialogues of an
+/// underlying storage in memory.
+///
+/// Up to `capacity` dialogues are kept in the cache; when it is full, the least
+/// recently used dialogue is evicted. [`Storage::get_dialogue`] is served from
+/// the cache if possible, while [`Storage::update_dialogue`] and
+/// [`Storage::remove_dialogue`] write through to the underlying storage.
+///
+/// All operations are performed under a single lock, so the cache never
+/// diverges from the underlying storage (as long as it is not modified by
+/// anyone else), at the cost of serializing access to it.
+///
+/// ## Example
+///
+/// ```
+/// use teloxide::dispatching::dialogue::{CachedStorage, InMemStorage};
+///
+/// // Usually, the underlying storage is a slower one, e.g. `RedisStorage`.
+/// let inner = InMemStorage::<String>::new();
+/// let storage = CachedStorage::<_, String>::new(inner, 1000);
+/// # let _ = storage;
+/// ```
+pub struct CachedStorage<S, D> {
+    inner: Arc<S>,
+    cache: Mutex<Lru<D>>,
+}
+
+impl<S, D> CachedStorage<S, D> {
+    /// Wraps `inner`, caching up to `capacity` dialogues.
+    ///
+    /// ## Panics
+    ///
+    /// If `capacity` is 0.
+    #[must_use]
+    pub fn new(inner: Arc<S>, capacity: usize) -> Arc<Self> {
+        assert_ne!(capacity, 0, "Capacity of a cache must be positive");
+
+        Arc::new(Self { inner, cache: Mutex::new(Lru::new(capacity)) })
+    }
+
+    /// Returns the underlying storage, dropping the cache.
+    pub fn into_inner(self) -> Arc<S> {
+        self.inner
+    }
+}
+
+impl<S, D> Storage<D> for CachedStorage<S, D>
+where
+    S: Storage<D> + Send + Sync + 'static,
+    D: Clone + Send + 'static,
+{
+    type Error = <S as Storage<D>>::Error;
+
+    fn remove_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+    ) -> BoxFuture<'static, Result<(), Self::Error>>
+    where
+        D: Send + 'static,
+    {
+        Box::pin(async move {
+            let mut cache = self.cache.lock().await;
+            cache.remove(chat_id);
+            <S as Storage<D>>::remove_dialogue(Arc::clone(&self.inner), chat_id).await
+        })
+    }
+
+    fn update_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+        dialogue: D,
+    ) -> BoxFuture<'static, Result<(), Self::Error>>
+    where
+        D: Send + 'static,
+    {
+        Box::pin(async move {
+            let mut cache = self.cache.lock().await;
+
+            // If the underlying storage fails, we don't know what is stored there anymore.
+            cache.remove(chat_id);
+            <S as Storage<D>>::update_dialogue(Arc::clone(&self.inner), chat_id, dialogue.clone())
+                .await?;
+            cache.insert(chat_id, dialogue);
+
+            Ok(())
+        })
+    }
+
+    fn get_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+    ) -> BoxFuture<'static, Result<Option<D>, Self::Error>> {
+        Box::pin(async move {
+            let mut cache = self.cache.lock().await;
+            if let Some(dialogue) = cache.get(chat_id) {
+                return Ok(Some(dialogue));
+            }
+
+            let dialogue =
+                <S as Storage<D>>::get_dialogue(Arc::clone(&self.inner), chat_id).await?;
+            if let Some(dialogue) = &dialogue {
+                cache.insert(chat_id, dialogue.clone());
+            }
+
+            Ok(dialogue)
+        })
+    }
+}
+
+impl<S, D> ListableStorage<D> for CachedStorage<S, D>
+where
+    S: ListableStorage<D> + Send + Sync + 'static,
+    D: Clone + Send + 'static,
+{
+    fn get_all_chat_ids(self: Arc<Self>) -> BoxFuture<'static, Result<Vec<ChatId>, Self::Error>> {
+        Box::pin(async move {
+            // The cache is write-through, so the underlying storage has all the dialogues.
+            let _cache = self.cache.lock().await;
+            <S as ListableStorage<D>>::get_all_chat_ids(Arc::clone(&self.inner)).await
+        })
+    }
+}
+
+/// A least recently used cache of dialogues.
+struct Lru<D> {
+    capacity: usize,
+    // Incremented on every access, used to order entries by recency.
+    clock: u64,
+    entries: HashMap<ChatId, (D, u64)>,
+    by_recency: BTreeMap<u64, ChatId>,
+}
+
+impl<D> Lru<D>
+where
+    D: Clone,
+{
+    fn new(capacity: usize) -> Self {
+        Self { capacity, clock: 0, entries: HashMap::new(), by_recency: BTreeMap::new() }
+    }
+
+    fn get(&mut self, chat_id: ChatId) -> Option<D> {
+        let now = self.tick();
+        let (dialogue, used_at) = self.entries.get_mut(&chat_id)?;
+
+        self.by_recency.remove(used_at);
+        self.by_recency.insert(now, chat_id);
+        *used_at = now;
+
+        Some(dialogue.clone())
+    }
+
+    fn insert(&mut self, chat_id: ChatId, dialogue: D) {
+        self.remove(chat_id);
+
+        if self.entries.len() == self.capacity {
+            if let Some(&oldest) = self.by_recency.keys().next() {
+                let evicted = self.by_recency.remove(&oldest).expect("The key was just found");
+                self.entries.remove(&evicted);
+            }
+        }
+
+        let now = self.tick();
+        self.entries.insert(chat_id, (dialogue, now));
+        self.by_recency.insert(now, chat_id);
+    }
+
+    fn remove(&mut self, chat_id: ChatId) {
+        if let Some((_, used_at)) = self.entries.remove(&chat_id) {
+            self.by_recency.remove(&used_at);
+        }
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatching::dialogue::InMemStorage;
+
+    #[tokio::test]
+    async fn test_cached_storage() {
+        let inner = InMemStorage::<i32>::new();
+        let cached = CachedStorage::new(Arc::clone(&inner), 2);
+
+        Arc::clone(&cached).update_dialogue(ChatId(1), 1).await.unwrap();
+        Arc::clone(&cached).update_dialogue(ChatId(2), 2).await.unwrap();
+        // Writes go through to the underlying storage.
+        assert_eq!(Arc::clone(&inner).get_dialogue(ChatId(1)).await.unwrap(), Some(1));
+
+        // Touch #1, so that #2 is evicted by #3.
+        assert_eq!(Arc::clone(&cached).get_dialogue(ChatId(1)).await.unwrap(), Some(1));
+        Arc::clone(&cached).update_dialogue(ChatId(3), 3).await.unwrap();
+
+        // Modify the underlying storage behind the cache's back to see what is cached.
+        Arc::clone(&inner).update_dialogue(ChatId(1), 10).await.unwrap();
+        Arc::clone(&inner).update_dialogue(ChatId(2), 20).await.unwrap();
+        assert_eq!(Arc::clone(&cached).get_dialogue(ChatId(1)).await.unwrap(), Some(1));
+        assert_eq!(Arc::clone(&cached).get_dialogue(ChatId(2)).await.unwrap(), Some(20));
+
+        Arc::clone(&cached).remove_dialogue(ChatId(3)).await.unwrap();
+        assert_eq!(Arc::clone(&cached).get_dialogue(ChatId(3)).await.unwrap(), None);
+        assert_eq!(Arc::clone(&inner).get_dialogue(ChatId(3)).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use teloxide_core::types::ChatId;
+
+use crate::dispatching::dialogue::Storage;
+
+/// A dialogue storage wrapper which forgets dialogues that were not updated for
+/// a specified period of time.
+///
+/// Each dialogue is stored in an underlying storage along with the time of its
+/// last update (see [`Timestamped`]). When a dialogue older than `ttl` is read,
+/// it is removed from the underlying storage and treated as absent, so e.g.
+/// [`enter_dialogue`] starts it from the default state. This way users that
+/// abandoned a dialogue mid-flow don't get stuck in it, without a separate task
+/// that cleans up the storage.
+///
+/// Time is measured with [`SystemTime`], so that expiration works across bot
+/// restarts when the underlying storage is persistent.
+///
+/// ## Example
+///
+/// ```
+/// use std::time::Duration;
+/// use teloxide::dispatching::dialogue::{ExpiringStorage, InMemStorage, Timestamped};
+///
+/// #[derive(Clone)]
+/// enum State {
+///     Start,
+///     ReceiveProductChoice,
+/// }
+///
+/// let storage = ExpiringStorage::<_, State>::with_on_expire(
+///     InMemStorage::<Timestamped<State>>::new(),
+///     Duration::from_secs(60 * 60),
+///     |chat_id, _expired_state| println!("The dialogue #{} has expired", chat_id),
+/// );
+/// # let _ = storage;
+/// ```
+///
+/// [`enter_dialogue`]: crate::dispatching::dialogue::enter
+pub struct ExpiringStorage<S, D> {
+    inner: Arc<S>,
+    ttl: Duration,
+    on_expire: Option<Box<dyn Fn(ChatId, D) + Send + Sync>>,
+}
+
+/// A dialogue along with the time of its last update, stored by
+/// [`ExpiringStorage`] in an underlying storage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timestamped<D> {
+    dialogue: D,
+    /// Milliseconds since [`UNIX_EPOCH`].
+    updated_at: u64,
+}
+
+impl<S, D> ExpiringStorage<S, D> {
+    /// Wraps `inner`, forgetting dialogues that were not updated for `ttl`.
+    #[must_use]
+    pub fn new(inner: Arc<S>, ttl: Duration) -> Arc<Self> {
+        Arc::new(Self { inner, ttl, on_expire: None })
+    }
+
+    /// The same as [`ExpiringStorage::new`], but also calls `on_expire` with an
+    /// expired dialogue when it is read and removed.
+    #[must_use]
+    pub fn with_on_expire<F>(inner: Arc<S>, ttl: Duration, on_expire: F) -> Arc<Self>
+    where
+        F: Fn(ChatId, D) + Send + Sync + 'static,
+    {
+        Arc::new(Self { inner, ttl, on_expire: Some(Box::new(on_expire)) })
+    }
+
+    /// Returns the underlying storage, whose dialogues are [`Timestamped`].
+    pub fn into_inner(self) -> Arc<S> {
+        self.inner
+    }
+}
+
+impl<S, D> Storage<D> for ExpiringStorage<S, D>
+where
+    S: Storage<Timestamped<D>> + Send + Sync + 'static,
+    D: Send + 'static,
+{
+    type Error = <S as Storage<Timestamped<D>>>::Error;
+
+    fn remove_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+    ) -> BoxFuture<'static, Result<(), Self::Error>>
+    where
+        D: Send + 'static,
+    {
+        <S as Storage<Timestamped<D>>>::remove_dialogue(Arc::clone(&self.inner), chat_id)
+    }
+
+    fn update_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+        dialogue: D,
+    ) -> BoxFuture<'static, Result<(), Self::Error>>
+    where
+        D: Send + 'static,
+    {
+        let dialogue = Timestamped { dialogue, updated_at: now_millis() };
+        <S as Storage<Timestamped<D>>>::update_dialogue(Arc::clone(&self.inner), chat_id, dialogue)
+    }
+
+    fn get_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+    ) -> BoxFuture<'static, Result<Option<D>, Self::Error>> {
+        Box::pin(async move {
+            let timestamped =
+                <S as Storage<Timestamped<D>>>::get_dialogue(Arc::clone(&self.inner), chat_id)
+                    .await?;
+            let Timestamped { dialogue, updated_at } = match timestamped {
+                Some(timestamped) => timestamped,
+                None => return Ok(None),
+            };
+
+            let age = Duration::from_millis(now_millis().saturating_sub(updated_at));
+            if age < self.ttl {
+                return Ok(Some(dialogue));
+            }
+
+            log::trace!("The dialogue #{} has expired ({:?} old)", chat_id, age);
+            if let Err(err) =
+                <S as Storage<Timestamped<D>>>::remove_dialogue(Arc::clone(&self.inner), chat_id)
+                    .await
+            {
+                // The dialogue may have been removed by a concurrent call, in
+                // which case `on_expire` is left to that call. Otherwise, the
+                // error is a real one.
+                let gone =
+                    <S as Storage<Timestamped<D>>>::get_dialogue(Arc::clone(&self.inner), chat_id)
+                        .await?
+                        .is_none();
+                return if gone { Ok(None) } else { Err(err) };
+            }
+            if let Some(on_expire) = &self.on_expire {
+                on_expire(chat_id, dialogue);
+            }
+
+            Ok(None)
+        })
+    }
+}
+
+fn now_millis() -> u64 {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    since_epoch.as_millis().try_into().expect("The time is too far in the future")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::dispatching::dialogue::InMemStorage;
+
+    #[tokio::test]
+    async fn test_expiring_storage() {
+        let chat_id = ChatId(123);
+
+        let fresh = ExpiringStorage::new(InMemStorage::new(), Duration::from_secs(60 * 60));
+        Arc::clone(&fresh).update_dialogue(chat_id, 1).await.unwrap();
+        assert_eq!(Arc::clone(&fresh).get_dialogue(chat_id).await.unwrap(), Some(1));
+        Arc::clone(&fresh).remove_dialogue(chat_id).await.unwrap();
+        assert_eq!(Arc::clone(&fresh).get_dialogue(chat_id).await.unwrap(), None);
+
+        let expired_count = Arc::new(AtomicUsize::new(0));
+        let inner = InMemStorage::<Timestamped<i32>>::new();
+        let expired = ExpiringStorage::with_on_expire(inner, Duration::ZERO, {
+            let expired_count = Arc::clone(&expired_count);
+            move |id, dialogue| {
+                assert_eq!((id, dialogue), (chat_id, 2));
+                expired_count.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        Arc::clone(&expired).update_dialogue(chat_id, 2).await.unwrap();
+        assert_eq!(Arc::clone(&expired).get_dialogue(chat_id).await.unwrap(), None);
+        assert_eq!(expired_count.load(Ordering::Relaxed), 1);
+
+        // The expired dialogue is removed, so `on_expire` is called only once.
+        assert_eq!(Arc::clone(&expired).get_dialogue(chat_id).await.unwrap(), None);
+        assert_eq!(expired_count.load(Ordering::Relaxed), 1);
+        assert!(Arc::clone(&expired.inner).get_dialogue(chat_id).await.unwrap().is_none());
+    }
+}
@@ -1,6 +1,6 @@
-use super::Storage;
+use super::{ListableStorage, Storage};
 use futures::future::BoxFuture;
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, hash::Hash, sync::Arc};
 use teloxide_core::types::ChatId;
 use thiserror::Error;
 use tokio::sync::Mutex;
@@ -19,29 +19,36 @@ pub enum InMemStorageError {
 /// All your dialogues will be lost after you restart your bot. If you need to
 /// store them somewhere on a drive, you should use e.g.
 /// [`super::SqliteStorage`] or implement your own.
+///
+/// Dialogues are indexed by chat IDs by default, but any hashable key `K` can
+/// be used instead, e.g. [`UserId`] with [`user_key`].
+///
+/// [`UserId`]: teloxide_core::types::UserId
+/// [`user_key`]: crate::dispatching::dialogue::user_key
 #[derive(Debug)]
-pub struct InMemStorage<D> {
-    map: Mutex<HashMap<ChatId, D>>,
+pub struct InMemStorage<D, K = ChatId> {
+    map: Mutex<HashMap<K, D>>,
 }
 
-impl<S> InMemStorage<S> {
+impl<S, K> InMemStorage<S, K>
+where
+    K: Hash + Eq,
+{
     #[must_use]
     pub fn new() -> Arc<Self> {
         Arc::new(Self { map: Mutex::new(HashMap::new()) })
     }
 }
 
-impl<D> Storage<D> for InMemStorage<D>
+impl<D, K> Storage<D, K> for InMemStorage<D, K>
 where
     D: Clone,
     D: Send + 'static,
+    K: Hash + Eq + Send + 'static,
 {
     type Error = InMemStorageError;
 
-    fn remove_dialogue(
-        self: Arc<Self>,
-        chat_id: ChatId,
-    ) -> BoxFuture<'static, Result<(), Self::Error>>
+    fn remove_dialogue(self: Arc<Self>, key: K) -> BoxFuture<'static, Result<(), Self::Error>>
     where
         D: Send + 'static,
     {
@@ -49,29 +56,36 @@ where
             self.map
                 .lock()
                 .await
-                .remove(&chat_id)
+                .remove(&key)
                 .map_or(Err(InMemStorageError::DialogueNotFound), |_| Ok(()))
         })
     }
 
     fn update_dialogue(
         self: Arc<Self>,
-        chat_id: ChatId,
+        key: K,
         dialogue: D,
     ) -> BoxFuture<'static, Result<(), Self::Error>>
     where
         D: Send + 'static,
     {
         Box::pin(async move {
-            self.map.lock().await.insert(chat_id, dialogue);
+            self.map.lock().await.insert(key, dialogue);
             Ok(())
         })
     }
 
-    fn get_dialogue(
-        self: Arc<Self>,
-        chat_id: ChatId,
-    ) -> BoxFuture<'static, Result<Option<D>, Self::Error>> {
-        Box::pin(async move { Ok(self.map.lock().await.get(&chat_id).map(ToOwned::to_owned)) })
+    fn get_dialogue(self: Arc<Self>, key: K) -> BoxFuture<'static, Result<Option<D>, Self::Error>> {
+        Box::pin(async move { Ok(self.map.lock().await.get(&key).map(ToOwned::to_owned)) })
+    }
+}
+
+impl<D> ListableStorage<D> for InMemStorage<D>
+where
+    D: Clone,
+    D: Send + 'static,
+{
+    fn get_all_chat_ids(self: Arc<Self>) -> BoxFuture<'static, Result<Vec<ChatId>, Self::Error>> {
+        Box::pin(async move { Ok(self.map.lock().await.keys().copied().collect()) })
     }
 }
@@ -1,7 +1,10 @@
 pub mod serializer;
 
+mod cached_storage;
+mod expiring_storage;
 mod in_mem_storage;
 mod trace_storage;
+mod transition_storage;
 
 #[cfg(feature = "redis-storage")]
 mod redis_storage;
@@ -9,12 +12,18 @@ mod redis_storage;
 #[cfg(feature = "sqlite-storage")]
 mod sqlite_storage;
 
+#[cfg(feature = "postgres-storage")]
+mod postgres_storage;
+
 use futures::future::BoxFuture;
 use teloxide_core::types::ChatId;
 
 pub use self::{
+    cached_storage::CachedStorage,
+    expiring_storage::{ExpiringStorage, Timestamped},
     in_mem_storage::{InMemStorage, InMemStorageError},
     trace_storage::TraceStorage,
+    transition_storage::{Transition, TransitionStorage},
 };
 
 #[cfg(feature = "redis-storage")]
@@ -25,9 +34,12 @@ use std::sync::Arc;
 #[cfg(feature = "sqlite-storage")]
 pub use sqlite_storage::{SqliteStorage, SqliteStorageError};
 
+#[cfg(feature = "postgres-storage")]
+pub use postgres_storage::{PostgresStorage, PostgresStorageBuilder, PostgresStorageError};
+
 /// A storage with an erased error type.
-pub type ErasedStorage<D> =
-    dyn Storage<D, Error = Box<dyn std::error::Error + Send + Sync>> + Send + Sync;
+pub type ErasedStorage<D, K = ChatId> =
+    dyn Storage<D, K, Error = Box<dyn std::error::Error + Send + Sync>> + Send + Sync;
 
 /// A storage of dialogues.
 ///
@@ -42,94 +54,123 @@ pub type ErasedStorage<D> =
 /// - [`InMemStorage`] -- a storage based on [`std::collections::HashMap`].
 /// - [`RedisStorage`] -- a Redis-based storage.
 /// - [`SqliteStorage`] -- an SQLite-based persistent storage.
+/// - [`PostgresStorage`] -- a PostgreSQL-based persistent storage.
+///
+/// Dialogues are indexed by a key `K`, which is a chat ID by default. Only
+/// [`InMemStorage`] supports other keys (see [`enter_with_key`]); the other
+/// storages and storage wrappers index dialogues by [`ChatId`].
 ///
 /// [`InMemStorage`]: crate::dispatching::dialogue::InMemStorage
 /// [`RedisStorage`]: crate::dispatching::dialogue::RedisStorage
 /// [`SqliteStorage`]: crate::dispatching::dialogue::SqliteStorage
-pub trait Storage<D> {
+/// [`PostgresStorage`]: crate::dispatching::dialogue::PostgresStorage
+/// [`enter_with_key`]: crate::dispatching::dialogue::enter_with_key
+pub trait Storage<D, K = ChatId> {
     type Error;
 
-    /// Removes a dialogue indexed by `chat_id`.
+    /// Removes a dialogue indexed by `key`.
     ///
-    /// If the dialogue indexed by `chat_id` does not exist, this function
-    /// results in an error.
+    /// If the dialogue indexed by `key` does not exist, this function results
+    /// in an error.
     #[must_use = "Futures are lazy and do nothing unless polled with .await"]
-    fn remove_dialogue(
-        self: Arc<Self>,
-        chat_id: ChatId,
-    ) -> BoxFuture<'static, Result<(), Self::Error>>
+    fn remove_dialogue(self: Arc<Self>, key: K) -> BoxFuture<'static, Result<(), Self::Error>>
     where
         D: Send + 'static;
 
-    /// Updates a dialogue indexed by `chat_id` with `dialogue`.
+    /// Updates a dialogue indexed by `key` with `dialogue`.
     #[must_use = "Futures are lazy and do nothing unless polled with .await"]
     fn update_dialogue(
         self: Arc<Self>,
-        chat_id: ChatId,
+        key: K,
         dialogue: D,
     ) -> BoxFuture<'static, Result<(), Self::Error>>
     where
         D: Send + 'static;
 
-    /// Returns the dialogue indexed by `chat_id`.
+    /// Returns the dialogue indexed by `key`.
     #[must_use = "Futures are lazy and do nothing unless polled with .await"]
-    fn get_dialogue(
-        self: Arc<Self>,
-        chat_id: ChatId,
-    ) -> BoxFuture<'static, Result<Option<D>, Self::Error>>;
+    fn get_dialogue(self: Arc<Self>, key: K) -> BoxFuture<'static, Result<Option<D>, Self::Error>>;
 
     /// Erases [`Self::Error`] to [`std::error::Error`].
     #[must_use]
-    fn erase(self: Arc<Self>) -> Arc<ErasedStorage<D>>
+    fn erase(self: Arc<Self>) -> Arc<ErasedStorage<D, K>>
     where
         Self: Sized + Send + Sync + 'static,
         Self::Error: std::error::Error + Send + Sync + 'static,
+        K: Send + 'static,
     {
         Arc::new(Eraser(self))
     }
 }
 
+/// A storage of dialogues which can list the chats that have one.
+///
+/// This is useful for administration tools, e.g. to count users that are in the
+/// middle of some dialogue. Listing may be slow on big storages, so don't use
+/// it in handlers of regular updates.
+///
+/// It is implemented by all the storages provided out of the box except for
+/// [`ExpiringStorage`], which doesn't know whether a dialogue has expired
+/// without reading it.
+///
+/// ## Example
+///
+/// Storages which are generic over a serializer implement this trait for each
+/// dialogue type, so you will need to specify it:
+///
+/// ```no_run
+/// use teloxide::dispatching::dialogue::{serializer::Json, ListableStorage, SqliteStorage};
+///
+/// # #[derive(serde::Serialize, serde::Deserialize)]
+/// # enum State {}
+/// # #[tokio::main]
+/// # async fn main() {
+/// let storage = SqliteStorage::open("db.sqlite", Json).await.unwrap();
+/// let chat_ids = ListableStorage::<State>::get_all_chat_ids(storage).await.unwrap();
+/// println!("{} users are in the middle of a dialogue", chat_ids.len());
+/// # }
+/// ```
+pub trait ListableStorage<D>: Storage<D> {
+    /// Returns IDs of all chats that have a dialogue stored, in no particular
+    /// order.
+    #[must_use = "Futures are lazy and do nothing unless polled with .await"]
+    fn get_all_chat_ids(self: Arc<Self>) -> BoxFuture<'static, Result<Vec<ChatId>, Self::Error>>;
+}
+
 struct Eraser<S>(Arc<S>);
 
-impl<D, S> Storage<D> for Eraser<S>
+impl<D, K, S> Storage<D, K> for Eraser<S>
 where
-    S: Storage<D> + Send + Sync + 'static,
+    K: Send + 'static,
+    S: Storage<D, K> + Send + Sync + 'static,
     S::Error: std::error::Error + Send + Sync + 'static,
 {
     type Error = Box<dyn std::error::Error + Send + Sync>;
 
-    fn remove_dialogue(
-        self: Arc<Self>,
-        chat_id: ChatId,
-    ) -> BoxFuture<'static, Result<(), Self::Error>>
+    fn remove_dialogue(self: Arc<Self>, key: K) -> BoxFuture<'static, Result<(), Self::Error>>
     where
         D: Send + 'static,
     {
         Box::pin(
-            async move { Arc::clone(&self.0).remove_dialogue(chat_id).await.map_err(|e| e.into()) },
+            async move { Arc::clone(&self.0).remove_dialogue(key).await.map_err(|e| e.into()) },
         )
     }
 
     fn update_dialogue(
         self: Arc<Self>,
-        chat_id: ChatId,
+        key: K,
         dialogue: D,
     ) -> BoxFuture<'static, Result<(), Self::Error>>
     where
         D: Send + 'static,
     {
         Box::pin(async move {
-            Arc::clone(&self.0).update_dialogue(chat_id, dialogue).await.map_err(|e| e.into())
+            Arc::clone(&self.0).update_dialogue(key, dialogue).await.map_err(|e| e.into())
         })
     }
 
-    fn get_dialogue(
-        self: Arc<Self>,
-        chat_id: ChatId,
-    ) -> BoxFuture<'static, Result<Option<D>, Self::Error>> {
-        Box::pin(
-            async move { Arc::clone(&self.0).get_dialogue(chat_id).await.map_err(|e| e.into()) },
-        )
+    fn get_dialogue(self: Arc<Self>, key: K) -> BoxFuture<'static, Result<Option<D>, Self::Error>> {
+        Box::pin(async move { Arc::clone(&self.0).get_dialogue(key).await.map_err(|e| e.into()) })
     }
 }
 
@@ -137,6 +178,17 @@ where
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_in_mem_chat_ids() {
+        let storage = InMemStorage::new();
+        Arc::clone(&storage).update_dialogue(ChatId(1), 1).await.unwrap();
+        Arc::clone(&storage).update_dialogue(ChatId(2), 2).await.unwrap();
+        Arc::clone(&storage).remove_dialogue(ChatId(1)).await.unwrap();
+
+        let chat_ids = Arc::clone(&storage).get_all_chat_ids().await.unwrap();
+        assert_eq!(chat_ids, [ChatId(2)]);
+    }
+
     #[tokio::test]
     async fn test_erased() {
         let chat_id = ChatId(123);
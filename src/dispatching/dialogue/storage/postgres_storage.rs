@@ -0,0 +1,260 @@
+use super::{serializer::Serializer, ListableStorage, Storage};
+use futures::future::BoxFuture;
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{
+    postgres::{PgPool, PgPoolOptions},
+    Executor,
+};
+use std::{
+    convert::Infallible,
+    fmt::{Debug, Display},
+    sync::Arc,
+};
+use teloxide_core::types::ChatId;
+use thiserror::Error;
+
+/// A persistent dialogue storage based on [PostgreSQL](https://www.postgresql.org/).
+pub struct PostgresStorage<S> {
+    pool: PgPool,
+    table_name: String,
+    serializer: S,
+}
+
+/// An error returned from [`PostgresStorage`].
+#[derive(Debug, Error)]
+pub enum PostgresStorageError<SE>
+where
+    SE: Debug + Display,
+{
+    #[error("dialogue serialization error: {0}")]
+    SerdeError(SE),
+
+    #[error("postgres error: {0}")]
+    PostgresError(#[from] sqlx::Error),
+
+    /// Returned from [`PostgresStorage::remove_dialogue`].
+    #[error("row not found")]
+    DialogueNotFound,
+}
+
+/// A builder for [`PostgresStorage`].
+///
+/// Use it when you need to tweak the default settings, e.g. to store dialogues
+/// of several bots in a single database by giving each bot its own table.
+///
+/// ## Example
+///
+/// ```no_run
+/// use teloxide::dispatching::dialogue::{serializer::Json, PostgresStorage};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let storage = PostgresStorage::builder("postgres://localhost/bots", Json)
+///     .table_name("shop_bot_dialogues")
+///     .max_connections(5)
+///     .open()
+///     .await
+///     .unwrap();
+/// # let _ = storage;
+/// # }
+/// ```
+pub struct PostgresStorageBuilder<S> {
+    database_url: String,
+    table_name: String,
+    max_connections: u32,
+    serializer: S,
+}
+
+impl<S> PostgresStorageBuilder<S> {
+    /// Sets the name of a table in which dialogues are stored.
+    ///
+    /// The table is created on [`open`] if it does not exist.
+    ///
+    /// By default, it is `teloxide_dialogues`.
+    ///
+    /// ## Panics
+    ///
+    /// If `table_name` is not a valid unquoted identifier, i.e. it is empty,
+    /// contains characters other than ASCII letters, digits, and underscores,
+    /// or starts with a digit.
+    ///
+    /// [`open`]: PostgresStorageBuilder::open
+    #[must_use]
+    pub fn table_name<T>(self, table_name: T) -> Self
+    where
+        T: Into<String>,
+    {
+        let table_name = table_name.into();
+        assert!(
+            table_name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && table_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+            "Invalid table name: {:?}",
+            table_name
+        );
+
+        Self { table_name, ..self }
+    }
+
+    /// Sets the maximum number of connections in the underlying pool.
+    ///
+    /// By default, it is 10.
+    #[must_use]
+    pub fn max_connections(self, max_connections: u32) -> Self {
+        Self { max_connections, ..self }
+    }
+
+    /// Connects to the database and creates the dialogues table if it does not
+    /// exist.
+    pub async fn open(self) -> Result<Arc<PostgresStorage<S>>, PostgresStorageError<Infallible>> {
+        let Self { database_url, table_name, max_connections, serializer } = self;
+
+        let pool =
+            PgPoolOptions::new().max_connections(max_connections).connect(&database_url).await?;
+
+        let mut conn = pool.acquire().await?;
+        let query = format!(
+            r#"
+CREATE TABLE IF NOT EXISTS {} (
+    chat_id BIGINT PRIMARY KEY,
+    dialogue BYTEA NOT NULL
+);
+        "#,
+            table_name
+        );
+        sqlx::query(&query).execute(&mut conn).await?;
+
+        Ok(Arc::new(PostgresStorage { pool, table_name, serializer }))
+    }
+}
+
+impl<S> PostgresStorage<S> {
+    /// Opens a storage with the default settings.
+    ///
+    /// This is the same as `PostgresStorage::builder(database_url,
+    /// serializer).open()`.
+    pub async fn open(
+        database_url: &str,
+        serializer: S,
+    ) -> Result<Arc<Self>, PostgresStorageError<Infallible>> {
+        Self::builder(database_url, serializer).open().await
+    }
+
+    /// Constructs a new [`PostgresStorageBuilder`].
+    #[must_use]
+    pub fn builder(database_url: &str, serializer: S) -> PostgresStorageBuilder<S> {
+        const DEFAULT_TABLE_NAME: &str = "teloxide_dialogues";
+        const DEFAULT_MAX_CONNECTIONS: u32 = 10;
+
+        PostgresStorageBuilder {
+            database_url: database_url.to_owned(),
+            table_name: DEFAULT_TABLE_NAME.to_owned(),
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            serializer,
+        }
+    }
+}
+
+impl<S, D> Storage<D> for PostgresStorage<S>
+where
+    S: Send + Sync + Serializer<D> + 'static,
+    D: Send + Serialize + DeserializeOwned + 'static,
+    <S as Serializer<D>>::Error: Debug + Display,
+{
+    type Error = PostgresStorageError<<S as Serializer<D>>::Error>;
+
+    fn remove_dialogue(
+        self: Arc<Self>,
+        ChatId(chat_id): ChatId,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        Box::pin(async move {
+            let query = format!("DELETE FROM {} WHERE chat_id = $1", self.table_name);
+            let deleted_rows_count =
+                sqlx::query(&query).bind(chat_id).execute(&self.pool).await?.rows_affected();
+
+            if deleted_rows_count == 0 {
+                return Err(PostgresStorageError::DialogueNotFound);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn update_dialogue(
+        self: Arc<Self>,
+        ChatId(chat_id): ChatId,
+        dialogue: D,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        Box::pin(async move {
+            let d =
+                self.serializer.serialize(&dialogue).map_err(PostgresStorageError::SerdeError)?;
+
+            let query = format!(
+                r#"
+            INSERT INTO {} VALUES ($1, $2)
+            ON CONFLICT(chat_id) DO UPDATE SET dialogue=excluded.dialogue
+                                "#,
+                self.table_name
+            );
+            self.pool.acquire().await?.execute(sqlx::query(&query).bind(chat_id).bind(d)).await?;
+            Ok(())
+        })
+    }
+
+    fn get_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+    ) -> BoxFuture<'static, Result<Option<D>, Self::Error>> {
+        Box::pin(async move {
+            get_dialogue(&self.pool, &self.table_name, chat_id)
+                .await?
+                .map(|d| self.serializer.deserialize(&d).map_err(PostgresStorageError::SerdeError))
+                .transpose()
+        })
+    }
+}
+
+impl<S, D> ListableStorage<D> for PostgresStorage<S>
+where
+    S: Send + Sync + Serializer<D> + 'static,
+    D: Send + Serialize + DeserializeOwned + 'static,
+    <S as Serializer<D>>::Error: Debug + Display,
+{
+    fn get_all_chat_ids(self: Arc<Self>) -> BoxFuture<'static, Result<Vec<ChatId>, Self::Error>> {
+        #[derive(sqlx::FromRow)]
+        struct ChatIdDbRow {
+            chat_id: i64,
+        }
+
+        Box::pin(async move {
+            let query = format!("SELECT chat_id FROM {}", self.table_name);
+            let chat_ids = sqlx::query_as::<_, ChatIdDbRow>(&query)
+                .fetch_all(&self.pool)
+                .await?
+                .into_iter()
+                .map(|r| ChatId(r.chat_id))
+                .collect();
+
+            Ok(chat_ids)
+        })
+    }
+}
+
+async fn get_dialogue(
+    pool: &PgPool,
+    table_name: &str,
+    ChatId(chat_id): ChatId,
+) -> Result<Option<Vec<u8>>, sqlx::Error> {
+    #[derive(sqlx::FromRow)]
+    struct DialogueDbRow {
+        dialogue: Vec<u8>,
+    }
+
+    let query = format!("SELECT dialogue FROM {} WHERE chat_id = $1", table_name);
+    let bytes = sqlx::query_as::<_, DialogueDbRow>(&query)
+        .bind(chat_id)
+        .fetch_optional(pool)
+        .await?
+        .map(|r| r.dialogue);
+
+    Ok(bytes)
+}
@@ -1,4 +1,4 @@
-use super::{serializer::Serializer, Storage};
+use super::{serializer::Serializer, ListableStorage, Storage};
 use futures::future::BoxFuture;
 use redis::{AsyncCommands, IntoConnectionInfo};
 use serde::{de::DeserializeOwned, Serialize};
@@ -32,19 +32,78 @@ where
 /// A dialogue storage based on [Redis](https://redis.io/).
 pub struct RedisStorage<S> {
     conn: Mutex<redis::aio::Connection>,
+    key_prefix: Option<String>,
     serializer: S,
 }
 
 impl<S> RedisStorage<S> {
+    /// Opens a storage which uses bare chat IDs as keys.
     pub async fn open(
         url: impl IntoConnectionInfo,
         serializer: S,
+    ) -> Result<Arc<Self>, RedisStorageError<Infallible>> {
+        Self::open_inner(url, None, serializer).await
+    }
+
+    /// Opens a storage which uses `{key_prefix}:{chat_id}` as keys.
+    ///
+    /// This allows several bots to share a single Redis instance without
+    /// their dialogues colliding.
+    pub async fn open_with_key_prefix(
+        url: impl IntoConnectionInfo,
+        key_prefix: impl Into<String>,
+        serializer: S,
+    ) -> Result<Arc<Self>, RedisStorageError<Infallible>> {
+        Self::open_inner(url, Some(key_prefix.into()), serializer).await
+    }
+
+    async fn open_inner(
+        url: impl IntoConnectionInfo,
+        key_prefix: Option<String>,
+        serializer: S,
     ) -> Result<Arc<Self>, RedisStorageError<Infallible>> {
         Ok(Arc::new(Self {
             conn: Mutex::new(redis::Client::open(url)?.get_async_connection().await?),
+            key_prefix,
             serializer,
         }))
     }
+
+    fn key(&self, ChatId(chat_id): ChatId) -> String {
+        match &self.key_prefix {
+            Some(key_prefix) => format!("{}:{}", key_prefix, chat_id),
+            // The same key as the one used before prefixes were introduced.
+            None => chat_id.to_string(),
+        }
+    }
+
+    /// Returns a `SCAN` pattern which matches keys of all dialogues (and maybe
+    /// some other keys, if there is no prefix).
+    fn key_pattern(&self) -> String {
+        match &self.key_prefix {
+            Some(key_prefix) => {
+                let mut pattern = String::with_capacity(key_prefix.len() + 2);
+                for c in key_prefix.chars() {
+                    if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+                        pattern.push('\\');
+                    }
+                    pattern.push(c);
+                }
+                pattern.push_str(":*");
+                pattern
+            }
+            None => "*".to_owned(),
+        }
+    }
+
+    fn chat_id_from_key(&self, key: &str) -> Option<ChatId> {
+        let chat_id = match &self.key_prefix {
+            Some(key_prefix) => key.strip_prefix(key_prefix.as_str())?.strip_prefix(':')?,
+            None => key,
+        };
+
+        chat_id.parse().ok().map(ChatId)
+    }
 }
 
 impl<S, D> Storage<D> for RedisStorage<S>
@@ -57,12 +116,12 @@ where
 
     fn remove_dialogue(
         self: Arc<Self>,
-        ChatId(chat_id): ChatId,
+        chat_id: ChatId,
     ) -> BoxFuture<'static, Result<(), Self::Error>> {
         Box::pin(async move {
             let deleted_rows_count = redis::pipe()
                 .atomic()
-                .del(chat_id)
+                .del(self.key(chat_id))
                 .query_async::<_, redis::Value>(self.conn.lock().await.deref_mut())
                 .await?;
 
@@ -83,29 +142,54 @@ where
 
     fn update_dialogue(
         self: Arc<Self>,
-        ChatId(chat_id): ChatId,
+        chat_id: ChatId,
         dialogue: D,
     ) -> BoxFuture<'static, Result<(), Self::Error>> {
         Box::pin(async move {
             let dialogue =
                 self.serializer.serialize(&dialogue).map_err(RedisStorageError::SerdeError)?;
-            self.conn.lock().await.set::<_, Vec<u8>, _>(chat_id, dialogue).await?;
+            self.conn.lock().await.set::<_, Vec<u8>, _>(self.key(chat_id), dialogue).await?;
             Ok(())
         })
     }
 
     fn get_dialogue(
         self: Arc<Self>,
-        ChatId(chat_id): ChatId,
+        chat_id: ChatId,
     ) -> BoxFuture<'static, Result<Option<D>, Self::Error>> {
         Box::pin(async move {
             self.conn
                 .lock()
                 .await
-                .get::<_, Option<Vec<u8>>>(chat_id)
+                .get::<_, Option<Vec<u8>>>(self.key(chat_id))
                 .await?
                 .map(|d| self.serializer.deserialize(&d).map_err(RedisStorageError::SerdeError))
                 .transpose()
         })
     }
 }
+
+impl<S, D> ListableStorage<D> for RedisStorage<S>
+where
+    S: Send + Sync + Serializer<D> + 'static,
+    D: Send + Serialize + DeserializeOwned + 'static,
+    <S as Serializer<D>>::Error: Debug + Display,
+{
+    /// Iterates over keys with `SCAN`, so it doesn't block the Redis server.
+    ///
+    /// Without a key prefix, all keys that are integers are considered chat
+    /// IDs.
+    fn get_all_chat_ids(self: Arc<Self>) -> BoxFuture<'static, Result<Vec<ChatId>, Self::Error>> {
+        Box::pin(async move {
+            let mut conn = self.conn.lock().await;
+            let mut keys = conn.scan_match::<_, String>(self.key_pattern()).await?;
+
+            let mut chat_ids = Vec::new();
+            while let Some(key) = keys.next_item().await {
+                chat_ids.extend(self.chat_id_from_key(&key));
+            }
+
+            Ok(chat_ids)
+        })
+    }
+}
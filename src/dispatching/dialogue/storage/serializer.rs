@@ -71,3 +71,54 @@ where
         bincode::deserialize(data)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::fmt::Debug;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum State {
+        Start,
+        ReceiveFullName(String),
+        ReceiveAge { full_name: String, age: Option<u8> },
+    }
+
+    fn states() -> Vec<State> {
+        vec![
+            State::Start,
+            State::ReceiveFullName("Ferris".to_owned()),
+            State::ReceiveAge { full_name: "Ferris the Crab".to_owned(), age: None },
+            State::ReceiveAge { full_name: String::new(), age: Some(7) },
+        ]
+    }
+
+    fn test_round_trip<S>(serializer: S)
+    where
+        S: Serializer<State>,
+        S::Error: Debug,
+    {
+        for state in states() {
+            let bytes = serializer.serialize(&state).unwrap();
+            assert_eq!(serializer.deserialize(&bytes).unwrap(), state);
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        test_round_trip(Json);
+    }
+
+    #[test]
+    #[cfg(feature = "cbor-serializer")]
+    fn cbor_round_trip() {
+        test_round_trip(Cbor);
+    }
+
+    #[test]
+    #[cfg(feature = "bincode-serializer")]
+    fn bincode_round_trip() {
+        test_round_trip(Bincode);
+    }
+}
@@ -1,4 +1,4 @@
-use super::{serializer::Serializer, Storage};
+use super::{serializer::Serializer, ListableStorage, Storage};
 use futures::future::BoxFuture;
 use serde::{de::DeserializeOwned, Serialize};
 use sqlx::{sqlite::SqlitePool, Executor};
@@ -124,6 +124,32 @@ where
     }
 }
 
+impl<S, D> ListableStorage<D> for SqliteStorage<S>
+where
+    S: Send + Sync + Serializer<D> + 'static,
+    D: Send + Serialize + DeserializeOwned + 'static,
+    <S as Serializer<D>>::Error: Debug + Display,
+{
+    fn get_all_chat_ids(self: Arc<Self>) -> BoxFuture<'static, Result<Vec<ChatId>, Self::Error>> {
+        #[derive(sqlx::FromRow)]
+        struct ChatIdDbRow {
+            chat_id: i64,
+        }
+
+        Box::pin(async move {
+            let chat_ids =
+                sqlx::query_as::<_, ChatIdDbRow>("SELECT chat_id FROM teloxide_dialogues")
+                    .fetch_all(&self.pool)
+                    .await?
+                    .into_iter()
+                    .map(|r| ChatId(r.chat_id))
+                    .collect();
+
+            Ok(chat_ids)
+        })
+    }
+}
+
 async fn get_dialogue(
     pool: &SqlitePool,
     ChatId(chat_id): ChatId,
@@ -7,7 +7,7 @@ use std::{
 use futures::future::BoxFuture;
 use teloxide_core::types::ChatId;
 
-use crate::dispatching::dialogue::Storage;
+use crate::dispatching::dialogue::{ListableStorage, Storage};
 
 /// A dialogue storage wrapper which logs all actions performed on an underlying
 /// storage.
@@ -70,3 +70,14 @@ where
         <S as Storage<D>>::get_dialogue(self.inner.clone(), chat_id)
     }
 }
+
+impl<S, D> ListableStorage<D> for TraceStorage<S>
+where
+    D: Debug,
+    S: ListableStorage<D> + Send + Sync + 'static,
+{
+    fn get_all_chat_ids(self: Arc<Self>) -> BoxFuture<'static, Result<Vec<ChatId>, Self::Error>> {
+        log::trace!("Requested all chat IDs");
+        <S as ListableStorage<D>>::get_all_chat_ids(self.inner.clone())
+    }
+}
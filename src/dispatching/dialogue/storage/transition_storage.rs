@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use teloxide_core::types::ChatId;
+
+use crate::dispatching::dialogue::{ListableStorage, Storage};
+
+/// A dialogue storage wrapper which reports all transitions between dialogue
+/// states to a callback.
+///
+/// A transition is reported after it has been successfully written to an
+/// underlying storage, i.e. on [`Dialogue::update`] and [`Dialogue::exit`]
+/// (and [`Dialogue::get_or_default`], if it stores a default state). Use it to
+/// collect analytics, e.g. how many users complete a dialogue and at which
+/// state the rest drop off.
+///
+/// To know the previous state, it is read from the underlying storage before
+/// each modification. This is consistent as long as the dialogue is not
+/// modified concurrently, which is guaranteed by [`Dispatcher`] as long as it
+/// distributes updates by chat (the default), since this storage indexes
+/// dialogues by chat IDs as well.
+///
+/// ## Example
+///
+/// ```
+/// use teloxide::dispatching::dialogue::{InMemStorage, TransitionStorage};
+///
+/// #[derive(Clone, Debug)]
+/// enum State {
+///     Start,
+///     ReceiveProductChoice,
+/// }
+///
+/// let storage = TransitionStorage::<_, State>::new(InMemStorage::new(), |transition| {
+///     log::info!(
+///         "The dialogue #{} has moved from {:?} to {:?}",
+///         transition.chat_id,
+///         transition.from,
+///         transition.to
+///     );
+/// });
+/// # let _ = storage;
+/// ```
+///
+/// [`Dialogue::update`]: crate::dispatching::dialogue::Dialogue::update
+/// [`Dialogue::exit`]: crate::dispatching::dialogue::Dialogue::exit
+/// [`Dialogue::get_or_default`]: crate::dispatching::dialogue::Dialogue::get_or_default
+/// [`Dispatcher`]: crate::dispatching::Dispatcher
+pub struct TransitionStorage<S, D> {
+    inner: Arc<S>,
+    on_transition: Box<dyn Fn(Transition<D>) + Send + Sync>,
+}
+
+/// A transition between dialogue states, reported by [`TransitionStorage`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transition<D> {
+    /// The chat of the dialogue.
+    pub chat_id: ChatId,
+
+    /// The previous state, or `None` if the dialogue has just been entered.
+    pub from: Option<D>,
+
+    /// The new state, or `None` if the dialogue has been exited.
+    pub to: Option<D>,
+}
+
+impl<S, D> TransitionStorage<S, D> {
+    /// Wraps `inner`, calling `on_transition` on each transition.
+    #[must_use]
+    pub fn new<F>(inner: Arc<S>, on_transition: F) -> Arc<Self>
+    where
+        F: Fn(Transition<D>) + Send + Sync + 'static,
+    {
+        Arc::new(Self { inner, on_transition: Box::new(on_transition) })
+    }
+
+    /// Returns the underlying storage, modifications of which are not reported.
+    pub fn into_inner(self) -> Arc<S> {
+        self.inner
+    }
+}
+
+impl<S, D> Storage<D> for TransitionStorage<S, D>
+where
+    S: Storage<D> + Send + Sync + 'static,
+    D: Clone + Send + 'static,
+{
+    type Error = <S as Storage<D>>::Error;
+
+    fn remove_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+    ) -> BoxFuture<'static, Result<(), Self::Error>>
+    where
+        D: Send + 'static,
+    {
+        Box::pin(async move {
+            let from = <S as Storage<D>>::get_dialogue(Arc::clone(&self.inner), chat_id).await?;
+            <S as Storage<D>>::remove_dialogue(Arc::clone(&self.inner), chat_id).await?;
+            (self.on_transition)(Transition { chat_id, from, to: None });
+
+            Ok(())
+        })
+    }
+
+    fn update_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+        dialogue: D,
+    ) -> BoxFuture<'static, Result<(), Self::Error>>
+    where
+        D: Send + 'static,
+    {
+        Box::pin(async move {
+            let from = <S as Storage<D>>::get_dialogue(Arc::clone(&self.inner), chat_id).await?;
+            <S as Storage<D>>::update_dialogue(Arc::clone(&self.inner), chat_id, dialogue.clone())
+                .await?;
+            (self.on_transition)(Transition { chat_id, from, to: Some(dialogue) });
+
+            Ok(())
+        })
+    }
+
+    fn get_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+    ) -> BoxFuture<'static, Result<Option<D>, Self::Error>> {
+        <S as Storage<D>>::get_dialogue(Arc::clone(&self.inner), chat_id)
+    }
+}
+
+impl<S, D> ListableStorage<D> for TransitionStorage<S, D>
+where
+    S: ListableStorage<D> + Send + Sync + 'static,
+    D: Clone + Send + 'static,
+{
+    fn get_all_chat_ids(self: Arc<Self>) -> BoxFuture<'static, Result<Vec<ChatId>, Self::Error>> {
+        <S as ListableStorage<D>>::get_all_chat_ids(Arc::clone(&self.inner))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::dispatching::dialogue::InMemStorage;
+
+    #[tokio::test]
+    async fn test_transition_storage() {
+        let chat_id = ChatId(123);
+        let transitions = Arc::new(Mutex::new(Vec::new()));
+
+        let storage = TransitionStorage::<_, i32>::new(InMemStorage::new(), {
+            let transitions = Arc::clone(&transitions);
+            move |transition| transitions.lock().unwrap().push(transition)
+        });
+        Arc::clone(&storage).update_dialogue(chat_id, 1).await.unwrap();
+        Arc::clone(&storage).update_dialogue(chat_id, 2).await.unwrap();
+        assert_eq!(Arc::clone(&storage).get_dialogue(chat_id).await.unwrap(), Some(2));
+        Arc::clone(&storage).remove_dialogue(chat_id).await.unwrap();
+
+        // A failed removal is not a transition.
+        assert!(Arc::clone(&storage).remove_dialogue(chat_id).await.is_err());
+
+        assert_eq!(
+            *transitions.lock().unwrap(),
+            [
+                Transition { chat_id, from: None, to: Some(1) },
+                Transition { chat_id, from: Some(1), to: Some(2) },
+                Transition { chat_id, from: Some(2), to: None },
+            ]
+        );
+    }
+}
@@ -1,7 +1,8 @@
 use crate::{
     dispatching::{
-        distribution::default_distribution_function, stop_token::StopToken, update_listeners,
-        update_listeners::UpdateListener, DefaultKey, DpHandlerDescription, ShutdownToken,
+        distribution::default_distribution_function, handler_description::all_allowed_updates,
+        stop_token::StopToken, update_listeners, update_listeners::UpdateListener, DefaultKey,
+        DpHandlerDescription, ShutdownToken,
     },
     error_handlers::{ErrorHandler, LoggingErrorHandler},
     requests::{Request, Requester},
@@ -10,16 +11,19 @@ use crate::{
 };
 
 use dptree::di::{DependencyMap, DependencySupplier};
-use futures::{future::BoxFuture, stream::FuturesUnordered, StreamExt};
+use futures::{future::BoxFuture, stream::FuturesUnordered, FutureExt, StreamExt};
 use std::{
     collections::HashMap,
     fmt::Debug,
     hash::Hash,
     ops::{ControlFlow, Deref},
+    panic::AssertUnwindSafe,
     sync::Arc,
+    time::Duration,
 };
-use tokio::time::timeout;
+use tokio::{sync::Semaphore, time::timeout};
 use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::CancellationToken;
 
 use std::future::Future;
 
@@ -29,9 +33,12 @@ pub struct DispatcherBuilder<R, Err, Key> {
     dependencies: DependencyMap,
     handler: Arc<UpdateHandler<Err>>,
     default_handler: DefaultHandler,
-    error_handler: Arc<dyn ErrorHandler<Err> + Send + Sync>,
+    error_handler: UpdateErrorHandler<Err>,
     distribution_f: fn(&Update) -> Option<Key>,
     worker_queue_size: usize,
+    max_concurrent_updates: Option<usize>,
+    shutdown_timeout: Option<Duration>,
+    allow_all_updates: bool,
 }
 
 impl<R, Err, Key> DispatcherBuilder<R, Err, Key>
@@ -62,9 +69,34 @@ where
     /// Specifies a handler that will be called on a handler error.
     ///
     /// By default, it is [`LoggingErrorHandler`].
+    ///
+    /// See also [`DispatcherBuilder::error_handler_with_update`].
     #[must_use]
     pub fn error_handler(self, handler: Arc<dyn ErrorHandler<Err> + Send + Sync>) -> Self {
-        Self { error_handler: handler, ..self }
+        Self { error_handler: ignoring_update(handler), ..self }
+    }
+
+    /// Specifies a handler that will be called on a handler error, along with
+    /// the update that caused it.
+    ///
+    /// Unlike [`DispatcherBuilder::error_handler`], this handler knows where
+    /// the error comes from, e.g. to tell a user in the corresponding chat
+    /// that something went wrong.
+    #[must_use]
+    pub fn error_handler_with_update<H, Fut>(self, handler: H) -> Self
+    where
+        H: Fn(Err, Arc<Update>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+
+        Self {
+            error_handler: Arc::new(move |err, upd| {
+                let handler = Arc::clone(&handler);
+                Box::pin(handler(err, upd))
+            }),
+            ..self
+        }
     }
 
     /// Specifies dependencies that can be used inside of handlers.
@@ -83,6 +115,57 @@ where
         Self { worker_queue_size: size, ..self }
     }
 
+    /// Limits the number of updates that are handled at the same time.
+    ///
+    /// Updates beyond the limit wait in the queues of workers (see
+    /// [`DispatcherBuilder::worker_queue_size`]) and, once those are full, in
+    /// the update listener, so no update is dropped. Updates from the same
+    /// chat are still handled sequentially.
+    ///
+    /// By default, there is no limit.
+    ///
+    /// ## Panics
+    ///
+    /// If `limit` is 0.
+    #[must_use]
+    pub fn max_concurrent_updates(self, limit: usize) -> Self {
+        assert_ne!(limit, 0, "The limit of concurrent updates must be positive");
+
+        Self { max_concurrent_updates: Some(limit), ..self }
+    }
+
+    /// Specifies how long to wait for the updates that are being processed when
+    /// dispatching stops.
+    ///
+    /// After the update listener is stopped (e.g., via
+    /// [`ShutdownToken::shutdown`]), [`Dispatcher`] waits until all the
+    /// already received updates are handled. If they are not handled within
+    /// `timeout`, the remaining handlers are aborted, and dispatching finishes
+    /// once they are dropped.
+    ///
+    /// By default, there is no timeout, i.e. [`Dispatcher`] waits for all
+    /// handlers to finish.
+    #[must_use]
+    pub fn shutdown_timeout(self, timeout: Duration) -> Self {
+        Self { shutdown_timeout: Some(timeout), ..self }
+    }
+
+    /// Requests all kinds of updates from the update listener.
+    ///
+    /// By default, [`Dispatcher`] derives the kinds of updates that the handler
+    /// can handle (e.g., [`Update::filter_message`] is only interested in
+    /// messages) and [hints] them to the update listener, so that Telegram
+    /// doesn't send updates which would be ignored anyway. Use this method to
+    /// opt out and receive every kind of updates, e.g. to handle them in
+    /// [`DispatcherBuilder::default_handler`].
+    ///
+    /// [`Update::filter_message`]: crate::dispatching::UpdateFilterExt::filter_message
+    /// [hints]: crate::dispatching::update_listeners::UpdateListener::hint_allowed_updates
+    #[must_use]
+    pub fn allow_all_updates(self) -> Self {
+        Self { allow_all_updates: true, ..self }
+    }
+
     /// Specifies the distribution function that decides how updates are grouped
     /// before execution.
     pub fn distribution_function<K>(
@@ -100,6 +183,9 @@ where
             error_handler,
             distribution_f: _,
             worker_queue_size,
+            max_concurrent_updates,
+            shutdown_timeout,
+            allow_all_updates,
         } = self;
 
         DispatcherBuilder {
@@ -110,6 +196,9 @@ where
             error_handler,
             distribution_f: f,
             worker_queue_size,
+            max_concurrent_updates,
+            shutdown_timeout,
+            allow_all_updates,
         }
     }
 
@@ -124,6 +213,9 @@ where
             error_handler,
             distribution_f,
             worker_queue_size,
+            max_concurrent_updates,
+            shutdown_timeout,
+            allow_all_updates,
         } = self;
 
         Dispatcher {
@@ -135,6 +227,11 @@ where
             state: ShutdownToken::new(),
             distribution_f,
             worker_queue_size,
+            concurrency_limit: max_concurrent_updates
+                .map(|max| ConcurrencyLimit { semaphore: Arc::new(Semaphore::new(max)), max }),
+            shutdown_timeout,
+            allow_all_updates,
+            cancellation_token: CancellationToken::new(),
             workers: HashMap::new(),
             default_worker: None,
         }
@@ -148,7 +245,20 @@ where
 /// determine a chat ID of an incoming update, it will be handled concurrently.
 /// Note that this behaviour can be altered with [`distribution_function`].
 ///
+/// Handling updates from the same chat sequentially means that handlers of a
+/// dialogue never race with each other: the next update is not handled until
+/// the previous one has been processed and its dialogue state has been
+/// written. This holds even if a handler panics — the panic is logged, and the
+/// chat continues receiving updates.
+///
+/// With the `tracing` feature enabled, each update is handled inside of a
+/// [`tracing`] span named `update`, with the `update_id`, `chat_id`, and
+/// `user_id` fields (the latter two are recorded if the update has them). Note
+/// that only events emitted via `tracing` are attributed to a span, not the
+/// ones emitted via `log`.
+///
 /// [`distribution_function`]: DispatcherBuilder::distribution_function
+/// [`tracing`]: https://docs.rs/tracing
 pub struct Dispatcher<R, Err, Key> {
     bot: R,
     dependencies: DependencyMap,
@@ -158,16 +268,28 @@ pub struct Dispatcher<R, Err, Key> {
 
     distribution_f: fn(&Update) -> Option<Key>,
     worker_queue_size: usize,
+    concurrency_limit: Option<ConcurrencyLimit>,
+    shutdown_timeout: Option<Duration>,
+    allow_all_updates: bool,
+    // The parent of cancellation tokens of all updates, cancelled on shutdown.
+    cancellation_token: CancellationToken,
     // Tokio TX channel parts associated with chat IDs that consume updates sequentially.
     workers: HashMap<Key, Worker>,
     // The default TX part that consume updates concurrently.
     default_worker: Option<Worker>,
 
-    error_handler: Arc<dyn ErrorHandler<Err> + Send + Sync>,
+    error_handler: UpdateErrorHandler<Err>,
 
     state: ShutdownToken,
 }
 
+/// A limit of updates that are handled at the same time by all workers.
+#[derive(Clone)]
+struct ConcurrencyLimit {
+    semaphore: Arc<Semaphore>,
+    max: usize,
+}
+
 struct Worker {
     tx: tokio::sync::mpsc::Sender<Update>,
     handle: tokio::task::JoinHandle<()>,
@@ -182,6 +304,18 @@ pub type UpdateHandler<Err> =
 
 type DefaultHandler = Arc<dyn Fn(Arc<Update>) -> BoxFuture<'static, ()> + Send + Sync>;
 
+type UpdateErrorHandler<Err> =
+    Arc<dyn Fn(Err, Arc<Update>) -> BoxFuture<'static, ()> + Send + Sync>;
+
+fn ignoring_update<Err>(
+    handler: Arc<dyn ErrorHandler<Err> + Send + Sync>,
+) -> UpdateErrorHandler<Err>
+where
+    Err: 'static,
+{
+    Arc::new(move |err, _upd| Arc::clone(&handler).handle_error(err))
+}
+
 impl<R, Err> Dispatcher<R, Err, DefaultKey>
 where
     R: Requester + Clone + Send + Sync + 'static,
@@ -203,9 +337,12 @@ where
                 log::warn!("Unhandled update: {:?}", upd);
                 Box::pin(async {})
             }),
-            error_handler: LoggingErrorHandler::new(),
+            error_handler: ignoring_update(LoggingErrorHandler::new()),
             worker_queue_size: DEFAULT_WORKER_QUEUE_SIZE,
+            max_concurrent_updates: None,
             distribution_f: default_distribution_function,
+            shutdown_timeout: None,
+            allow_all_updates: false,
         }
     }
 }
@@ -227,11 +364,14 @@ where
     ///
     ///  - Your bot passed to [`Dispatcher::builder`];
     ///  - An update from Telegram;
-    ///  - [`crate::types::Me`] (can be used in [`HandlerExt::filter_command`]).
+    ///  - [`crate::types::Me`] (can be used in [`HandlerExt::filter_command`]);
+    ///  - [`CancellationToken`], which is cancelled once dispatching starts
+    ///    shutting down, so long-running handlers can bail out early.
     ///
     /// [`shutdown`]: ShutdownToken::shutdown
     /// [a ctrlc signal]: Dispatcher::setup_ctrlc_handler
     /// [`HandlerExt::filter_command`]: crate::dispatching::HandlerExt::filter_command
+    /// [`CancellationToken`]: crate::dispatching::CancellationToken
     pub async fn dispatch(&mut self)
     where
         R: Requester + Clone,
@@ -249,6 +389,9 @@ where
     ///
     /// This method adds the same dependencies as [`Dispatcher::dispatch`].
     ///
+    /// The kinds of updates that the handler can handle are hinted to
+    /// `update_listener` (see [`DispatcherBuilder::allow_all_updates`]).
+    ///
     /// [`shutdown`]: ShutdownToken::shutdown
     /// [a ctrlc signal]: Dispatcher::setup_ctrlc_handler
     pub async fn dispatch_with_listener<'a, UListener, ListenerE, Eh>(
@@ -265,14 +408,20 @@ where
         self.dependencies.insert(me);
         self.dependencies.insert(self.bot.clone());
 
-        let description = self.handler.description();
-        let allowed_updates = description.allowed_updates();
+        let allowed_updates = if self.allow_all_updates {
+            all_allowed_updates()
+        } else {
+            self.handler.description().allowed_updates()
+        };
         log::debug!("hinting allowed updates: {:?}", allowed_updates);
         update_listener.hint_allowed_updates(&mut allowed_updates.into_iter());
 
         let shutdown_check_timeout = shutdown_check_timeout_for(&update_listener);
         let mut stop_token = Some(update_listener.stop_token());
 
+        // A token from the previous dispatching might be already cancelled.
+        self.cancellation_token = CancellationToken::new();
+
         self.state.start_dispatching();
 
         {
@@ -289,26 +438,61 @@ where
                     }
                 }
 
-                if self.state.is_shutting_down() {
-                    if let Some(token) = stop_token.take() {
-                        log::debug!("Start shutting down dispatching...");
-                        token.stop();
-                    }
-                }
+                self.stop_listener_if_shutting_down(&mut stop_token);
             }
         }
 
-        self.workers
+        self.stop_workers().await;
+
+        self.state.done();
+    }
+
+    /// Stops the update listener and cancels the tokens of all updates once
+    /// [`ShutdownToken::shutdown`] is called.
+    fn stop_listener_if_shutting_down<T>(&self, stop_token: &mut Option<T>)
+    where
+        T: StopToken,
+    {
+        if self.state.is_shutting_down() {
+            if let Some(token) = stop_token.take() {
+                log::debug!("Start shutting down dispatching...");
+                token.stop();
+                self.cancellation_token.cancel();
+            }
+        }
+    }
+
+    /// Waits until workers handle the already received updates, aborting them
+    /// after `shutdown_timeout`.
+    async fn stop_workers(&mut self) {
+        // Dropping TX parts makes workers finish once their queues are empty.
+        let mut handles = self
+            .workers
             .drain()
             .map(|(_chat_id, worker)| worker.handle)
             .chain(self.default_worker.take().map(|worker| worker.handle))
-            .collect::<FuturesUnordered<_>>()
-            .for_each(|res| async {
-                res.expect("Failed to wait for a worker.");
-            })
-            .await;
+            .collect::<FuturesUnordered<_>>();
 
-        self.state.done();
+        let shutdown_timeout = match self.shutdown_timeout {
+            Some(shutdown_timeout) => shutdown_timeout,
+            None => return wait_for_workers(&mut handles).await,
+        };
+
+        if timeout(shutdown_timeout, wait_for_workers(&mut handles)).await.is_ok() {
+            return;
+        }
+
+        log::warn!("Handlers did not finish within {:?}, aborting them...", shutdown_timeout);
+        handles.iter().for_each(|handle| handle.abort());
+
+        // Aborting only schedules cancellation, so wait until the handlers are
+        // actually dropped, e.g. have released the resources they hold.
+        while let Some(res) = handles.next().await {
+            match res {
+                Err(err) if err.is_cancelled() => {}
+                res => res.expect("Failed to wait for a worker."),
+            }
+        }
     }
 
     async fn process_update<LErr, LErrHandler>(
@@ -336,12 +520,16 @@ where
                         let handler = Arc::clone(&self.handler);
                         let default_handler = Arc::clone(&self.default_handler);
                         let error_handler = Arc::clone(&self.error_handler);
+                        let cancellation_token = self.cancellation_token.clone();
+                        let concurrency_limit = self.concurrency_limit.clone();
 
                         spawn_worker(
                             deps,
                             handler,
                             default_handler,
                             error_handler,
+                            cancellation_token,
+                            concurrency_limit,
                             self.worker_queue_size,
                         )
                     }),
@@ -350,12 +538,16 @@ where
                         let handler = Arc::clone(&self.handler);
                         let default_handler = Arc::clone(&self.default_handler);
                         let error_handler = Arc::clone(&self.error_handler);
+                        let cancellation_token = self.cancellation_token.clone();
+                        let concurrency_limit = self.concurrency_limit.clone();
 
                         spawn_default_worker(
                             deps,
                             handler,
                             default_handler,
                             error_handler,
+                            cancellation_token,
+                            concurrency_limit,
                             self.worker_queue_size,
                         )
                     }),
@@ -400,11 +592,19 @@ where
     }
 }
 
+async fn wait_for_workers(handles: &mut FuturesUnordered<tokio::task::JoinHandle<()>>) {
+    while let Some(res) = handles.next().await {
+        res.expect("Failed to wait for a worker.");
+    }
+}
+
 fn spawn_worker<Err>(
     deps: DependencyMap,
     handler: Arc<UpdateHandler<Err>>,
     default_handler: DefaultHandler,
-    error_handler: Arc<dyn ErrorHandler<Err> + Send + Sync>,
+    error_handler: UpdateErrorHandler<Err>,
+    cancellation_token: CancellationToken,
+    concurrency_limit: Option<ConcurrencyLimit>,
     queue_size: usize,
 ) -> Worker
 where
@@ -419,8 +619,18 @@ where
         let handler = Arc::clone(&handler);
         let default_handler = Arc::clone(&default_handler);
         let error_handler = Arc::clone(&error_handler);
+        let cancellation_token = cancellation_token.child_token();
+        let concurrency_limit = concurrency_limit.clone();
 
-        handle_update(update, deps, handler, default_handler, error_handler)
+        handle_update(
+            update,
+            deps,
+            handler,
+            default_handler,
+            error_handler,
+            cancellation_token,
+            concurrency_limit,
+        )
     }));
 
     Worker { tx, handle }
@@ -430,7 +640,9 @@ fn spawn_default_worker<Err>(
     deps: DependencyMap,
     handler: Arc<UpdateHandler<Err>>,
     default_handler: DefaultHandler,
-    error_handler: Arc<dyn ErrorHandler<Err> + Send + Sync>,
+    error_handler: UpdateErrorHandler<Err>,
+    cancellation_token: CancellationToken,
+    concurrency_limit: Option<ConcurrencyLimit>,
     queue_size: usize,
 ) -> Worker
 where
@@ -440,14 +652,28 @@ where
 
     let deps = Arc::new(deps);
 
-    let handle = tokio::spawn(ReceiverStream::new(rx).for_each_concurrent(None, move |update| {
-        let deps = Arc::clone(&deps);
-        let handler = Arc::clone(&handler);
-        let default_handler = Arc::clone(&default_handler);
-        let error_handler = Arc::clone(&error_handler);
+    // Don't take more updates from the queue than can be handled at once.
+    let max_concurrent = concurrency_limit.as_ref().map(|limit| limit.max);
 
-        handle_update(update, deps, handler, default_handler, error_handler)
-    }));
+    let handle =
+        tokio::spawn(ReceiverStream::new(rx).for_each_concurrent(max_concurrent, move |update| {
+            let deps = Arc::clone(&deps);
+            let handler = Arc::clone(&handler);
+            let default_handler = Arc::clone(&default_handler);
+            let error_handler = Arc::clone(&error_handler);
+            let cancellation_token = cancellation_token.child_token();
+            let concurrency_limit = concurrency_limit.clone();
+
+            handle_update(
+                update,
+                deps,
+                handler,
+                default_handler,
+                error_handler,
+                cancellation_token,
+                concurrency_limit,
+            )
+        }));
 
     Worker { tx, handle }
 }
@@ -457,30 +683,339 @@ async fn handle_update<Err>(
     deps: Arc<DependencyMap>,
     handler: Arc<UpdateHandler<Err>>,
     default_handler: DefaultHandler,
-    error_handler: Arc<dyn ErrorHandler<Err> + Send + Sync>,
+    error_handler: UpdateErrorHandler<Err>,
+    cancellation_token: CancellationToken,
+    concurrency_limit: Option<ConcurrencyLimit>,
+) where
+    Err: Send + Sync + 'static,
+{
+    let _permit = match &concurrency_limit {
+        Some(limit) => {
+            Some(limit.semaphore.acquire().await.expect("The semaphore is never closed"))
+        }
+        None => None,
+    };
+
+    #[cfg(feature = "tracing")]
+    {
+        use tracing::Instrument;
+
+        let span = update_span(&update);
+        dispatch_update(update, deps, handler, default_handler, error_handler, cancellation_token)
+            .instrument(span)
+            .await
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    dispatch_update(update, deps, handler, default_handler, error_handler, cancellation_token).await
+}
+
+/// Creates a span in which all the events of handling `update` are recorded.
+#[cfg(feature = "tracing")]
+fn update_span(update: &Update) -> tracing::Span {
+    let span = tracing::info_span!(
+        "update",
+        update_id = update.id,
+        chat_id = tracing::field::Empty,
+        user_id = tracing::field::Empty,
+    );
+    if let Some(chat) = update.chat() {
+        span.record("chat_id", &chat.id.0);
+    }
+    if let Some(user) = update.user() {
+        span.record("user_id", &user.id.0);
+    }
+
+    span
+}
+
+async fn dispatch_update<Err>(
+    update: Update,
+    deps: Arc<DependencyMap>,
+    handler: Arc<UpdateHandler<Err>>,
+    default_handler: DefaultHandler,
+    error_handler: UpdateErrorHandler<Err>,
+    cancellation_token: CancellationToken,
 ) where
     Err: Send + Sync + 'static,
 {
     let mut deps = deps.deref().clone();
     deps.insert(update);
+    deps.insert(cancellation_token);
+    let update: Arc<Update> = deps.get();
 
-    match handler.dispatch(deps).await {
-        ControlFlow::Break(Ok(())) => {}
-        ControlFlow::Break(Err(err)) => error_handler.clone().handle_error(err).await,
-        ControlFlow::Continue(deps) => {
-            let update = deps.get();
-            (default_handler)(update).await;
+    // A panicking handler must not take down the worker, otherwise the chat would
+    // stop receiving updates (and the dispatcher would panic on sending one).
+    let res = match AssertUnwindSafe(handler.dispatch(deps)).catch_unwind().await {
+        Ok(res) => res,
+        Err(_) => {
+            log::error!("A handler panicked while processing the update #{}", update.id);
+            return;
         }
+    };
+
+    match res {
+        ControlFlow::Break(Ok(())) => {}
+        ControlFlow::Break(Err(err)) => (error_handler)(err, update).await,
+        ControlFlow::Continue(_) => (default_handler)(update).await,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::convert::Infallible;
+    use std::{
+        convert::Infallible,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Mutex,
+        },
+    };
 
     use teloxide_core::Bot;
 
     use super::*;
+    use crate::dispatching::stop_token::AsyncStopToken;
+
+    fn message(update_id: i32, chat_id: i64) -> Update {
+        let update: Update = serde_json::from_value(serde_json::json!({
+            "update_id": update_id,
+            "message": {
+                "message_id": update_id,
+                "date": 0,
+                "chat": { "id": chat_id, "type": "private", "first_name": "User" },
+                "from": { "id": chat_id, "is_bot": false, "first_name": "User" },
+                "text": "text",
+            },
+        }))
+        .unwrap();
+        assert!(!matches!(update.kind, UpdateKind::Error(_)));
+
+        update
+    }
+
+    /// Counts handlers that are running at the same time.
+    #[derive(Default)]
+    struct InFlight {
+        current: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    impl InFlight {
+        async fn run<F: Future>(&self, handler: F) -> F::Output {
+            let current = self.current.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(current, Ordering::SeqCst);
+            let output = handler.await;
+            self.current.fetch_sub(1, Ordering::SeqCst);
+
+            output
+        }
+
+        fn peak(&self) -> usize {
+            self.peak.load(Ordering::SeqCst)
+        }
+    }
+
+    async fn process<Err, Key>(dispatcher: &mut Dispatcher<Bot, Err, Key>, update: Update)
+    where
+        Err: Send + Sync + 'static,
+        Key: Hash + Eq,
+    {
+        dispatcher.process_update(Ok::<_, Infallible>(update), &LoggingErrorHandler::new()).await;
+    }
+
+    #[tokio::test]
+    async fn updates_from_one_chat_are_handled_sequentially() {
+        let all = Arc::new(InFlight::default());
+        let per_chat = Arc::new([InFlight::default(), InFlight::default()]);
+        let handler = dptree::endpoint({
+            let all = Arc::clone(&all);
+            let per_chat = Arc::clone(&per_chat);
+            move |update: Update| {
+                let all = Arc::clone(&all);
+                let per_chat = Arc::clone(&per_chat);
+                async move {
+                    let chat = &per_chat[update.chat().unwrap().id.0 as usize];
+                    all.run(chat.run(tokio::time::sleep(Duration::from_millis(10)))).await;
+                    Ok::<_, Infallible>(())
+                }
+            }
+        });
+
+        let mut dispatcher = Dispatcher::builder(Bot::new(""), handler).build();
+        for id in 0..6 {
+            process(&mut dispatcher, message(id, i64::from(id % 2))).await;
+        }
+        dispatcher.stop_workers().await;
+
+        assert_eq!(per_chat[0].peak(), 1);
+        assert_eq!(per_chat[1].peak(), 1);
+        assert_eq!(all.peak(), 2);
+    }
+
+    #[tokio::test]
+    async fn max_concurrent_updates() {
+        let in_flight = Arc::new(InFlight::default());
+        let handler = dptree::endpoint({
+            let in_flight = Arc::clone(&in_flight);
+            move || {
+                let in_flight = Arc::clone(&in_flight);
+                async move {
+                    in_flight.run(tokio::time::sleep(Duration::from_millis(10))).await;
+                    Ok::<_, Infallible>(())
+                }
+            }
+        });
+
+        let mut dispatcher =
+            Dispatcher::builder(Bot::new(""), handler).max_concurrent_updates(2).build();
+        // Updates from different chats would be handled concurrently otherwise.
+        for id in 0..6 {
+            process(&mut dispatcher, message(id, i64::from(id))).await;
+        }
+        dispatcher.stop_workers().await;
+
+        assert_eq!(in_flight.peak(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "The limit of concurrent updates must be positive")]
+    fn max_concurrent_updates_zero() {
+        let handler = dptree::endpoint(|| async { Ok::<_, Infallible>(()) });
+        let _ = Dispatcher::builder(Bot::new(""), handler).max_concurrent_updates(0);
+    }
+
+    #[tokio::test]
+    async fn panicking_handler_keeps_worker_alive() {
+        let handled = Arc::new(Mutex::new(Vec::new()));
+        let handler = dptree::endpoint({
+            let handled = Arc::clone(&handled);
+            move |update: Update| {
+                let handled = Arc::clone(&handled);
+                async move {
+                    if update.id == 1 {
+                        panic!("Oops");
+                    }
+                    handled.lock().unwrap().push(update.id);
+                    Ok::<_, Infallible>(())
+                }
+            }
+        });
+
+        let mut dispatcher = Dispatcher::builder(Bot::new(""), handler).build();
+        process(&mut dispatcher, message(1, 1)).await;
+        process(&mut dispatcher, message(2, 1)).await;
+        dispatcher.stop_workers().await;
+
+        // The next update from the same chat is handled by the same worker.
+        assert_eq!(*handled.lock().unwrap(), [2]);
+    }
+
+    #[tokio::test]
+    async fn error_handler_with_update() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let handler = dptree::endpoint(|update: Update| async move { Err(update.id) });
+
+        let mut dispatcher = Dispatcher::builder(Bot::new(""), handler)
+            .error_handler_with_update({
+                let errors = Arc::clone(&errors);
+                move |err, update: Arc<Update>| {
+                    errors.lock().unwrap().push((err, update.id));
+                    async {}
+                }
+            })
+            .build();
+        process(&mut dispatcher, message(1, 1)).await;
+        process(&mut dispatcher, message(2, 2)).await;
+        dispatcher.stop_workers().await;
+
+        let mut errors = errors.lock().unwrap().clone();
+        errors.sort_unstable();
+        assert_eq!(errors, [(1, 1), (2, 2)]);
+    }
+
+    #[tokio::test]
+    async fn error_handler_ignoring_update() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let unhandled = Arc::new(AtomicUsize::new(0));
+        let handler = dptree::endpoint(|update: Update| async move { Err(update.id) });
+
+        let mut dispatcher = Dispatcher::builder(Bot::new(""), handler)
+            .error_handler(Arc::new({
+                let errors = Arc::clone(&errors);
+                move |err: i32| {
+                    errors.lock().unwrap().push(err);
+                    async {}
+                }
+            }))
+            .default_handler({
+                let unhandled = Arc::clone(&unhandled);
+                move |_| {
+                    unhandled.fetch_add(1, Ordering::SeqCst);
+                    async {}
+                }
+            })
+            .build();
+        process(&mut dispatcher, message(1, 1)).await;
+        dispatcher.stop_workers().await;
+
+        // The error is passed on as is, and the update is not handled otherwise.
+        assert_eq!(*errors.lock().unwrap(), [1]);
+        assert_eq!(unhandled.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn cancellation_token_is_cancelled_on_shutdown() {
+        let handler = dptree::endpoint(|token: CancellationToken| async move {
+            token.cancelled().await;
+            Ok::<_, Infallible>(())
+        });
+
+        let mut dispatcher = Dispatcher::builder(Bot::new(""), handler).build();
+        let shutdown_token = dispatcher.shutdown_token();
+        dispatcher.state.start_dispatching();
+        process(&mut dispatcher, message(1, 1)).await;
+
+        let _ = shutdown_token.shutdown().unwrap();
+        let (stop_token, _flag) = AsyncStopToken::new_pair();
+        dispatcher.stop_listener_if_shutting_down(&mut Some(stop_token));
+
+        timeout(Duration::from_secs(10), dispatcher.stop_workers())
+            .await
+            .expect("The handler has not been cancelled");
+    }
+
+    #[tokio::test]
+    async fn shutdown_timeout_aborts_handlers() {
+        struct DropFlag(Arc<AtomicBool>);
+
+        impl Drop for DropFlag {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let dropped = Arc::new(AtomicBool::new(false));
+        let handler = dptree::endpoint({
+            let dropped = Arc::clone(&dropped);
+            move || {
+                let flag = DropFlag(Arc::clone(&dropped));
+                async move {
+                    let _flag = flag;
+                    futures::future::pending::<()>().await;
+                    Ok::<_, Infallible>(())
+                }
+            }
+        });
+
+        let mut dispatcher = Dispatcher::builder(Bot::new(""), handler)
+            .shutdown_timeout(Duration::from_millis(10))
+            .build();
+        process(&mut dispatcher, message(1, 1)).await;
+        dispatcher.stop_workers().await;
+
+        // The hanging handler is not only aborted, but also dropped.
+        assert!(dropped.load(Ordering::SeqCst));
+    }
 
     #[tokio::test]
     async fn test_tokio_spawn() {
@@ -78,12 +78,18 @@ define_message_ext! {
     (filter_poll, Message::poll),
     (filter_sticker, Message::sticker),
     (filter_text, Message::text),
+    (filter_venue, Message::venue),
+    (filter_video, Message::video),
+    (filter_video_note, Message::video_note),
+    (filter_voice, Message::voice),
     (filter_reply_to_message, Message::reply_to_message),
     (filter_forward_from, Message::forward_from),
     (filter_new_chat_members, Message::new_chat_members),
     (filter_left_chat_member, Message::left_chat_member),
     (filter_pinned, Message::pinned_message),
     (filter_dice, Message::dice),
+    (filter_invoice, Message::invoice),
+    (filter_successful_payment, Message::successful_payment),
 }
 
 macro_rules! define_update_ext {
@@ -18,30 +18,35 @@ impl DpHandlerDescription {
     }
 
     pub(crate) fn allowed_updates(&self) -> Vec<AllowedUpdate> {
-        use AllowedUpdate::*;
-
         match &self.allowed {
             EventKind::InterestList(set) => set.iter().copied().collect(),
             EventKind::Entry => panic!("No updates were allowed"),
-            EventKind::UserDefined => vec![
-                Message,
-                EditedMessage,
-                ChannelPost,
-                EditedChannelPost,
-                InlineQuery,
-                ChosenInlineResult,
-                CallbackQuery,
-                ShippingQuery,
-                PreCheckoutQuery,
-                Poll,
-                PollAnswer,
-                MyChatMember,
-                ChatMember,
-            ],
+            EventKind::UserDefined => all_allowed_updates(),
         }
     }
 }
 
+/// Returns all kinds of updates.
+pub(crate) fn all_allowed_updates() -> Vec<AllowedUpdate> {
+    use AllowedUpdate::*;
+
+    vec![
+        Message,
+        EditedMessage,
+        ChannelPost,
+        EditedChannelPost,
+        InlineQuery,
+        ChosenInlineResult,
+        CallbackQuery,
+        ShippingQuery,
+        PreCheckoutQuery,
+        Poll,
+        PollAnswer,
+        MyChatMember,
+        ChatMember,
+    ]
+}
+
 impl HandlerDescription for DpHandlerDescription {
     fn entry() -> Self {
         Self { allowed: HandlerDescription::entry() }
@@ -3,20 +3,33 @@ use crate::{
         dialogue::{GetChatId, Storage},
         DpHandlerDescription,
     },
-    types::{Me, Message},
-    utils::command::BotCommands,
+    types::{CallbackQuery, Chat, Me, Message, MessageEntityKind, Update, UserId},
+    utils::command::{self, BotCommands, ParseError},
+};
+use dptree::{
+    di::{DependencyMap, Injectable},
+    Handler, HandlerDescription,
 };
-use dptree::{di::DependencyMap, Handler};
 
 #[allow(deprecated)]
 use crate::dispatching::HandlerFactory;
 
-use std::fmt::Debug;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Debug,
+    ops::{ControlFlow, Range},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 /// Extension methods for working with `dptree` handlers.
 pub trait HandlerExt<Output> {
     /// Returns a handler that accepts a parsed command `C`.
     ///
+    /// The command is parsed by [`BotCommands::parse_with_bot_name`], i.e. the
+    /// `@bot_username` suffix is compared case-insensitively.
+    ///
     /// ## Dependency requirements
     ///
     ///  - [`crate::types::Message`]
@@ -26,6 +39,191 @@ pub trait HandlerExt<Output> {
     where
         C: BotCommands + Send + Sync + 'static;
 
+    /// Returns a handler that accepts the result of parsing a command `C`.
+    ///
+    /// Unlike [`HandlerExt::filter_command`], this handler does not skip text
+    /// messages that fail to parse: it passes `Result<C, Arc<ParseError>>`
+    /// forwards, so that you can tell a user what is wrong with the command
+    /// (e.g., [`ParseError::TooFewArguments`]).
+    ///
+    /// Messages without text, text messages which are not commands at all
+    /// (i.e. don't start with a prefix of `C`), and commands addressed to
+    /// another bot ([`ParseError::WrongBotName`]) are skipped.
+    ///
+    /// ## Dependency requirements
+    ///
+    ///  - [`crate::types::Message`]
+    ///  - [`crate::types::Me`]
+    #[must_use]
+    fn filter_command_with_error<C>(self) -> Self
+    where
+        C: BotCommands + Send + Sync + 'static;
+
+    /// Returns a handler that accepts text messages which mention the bot.
+    ///
+    /// A mention is either a `@bot_username` ([`MessageEntityKind::Mention`],
+    /// compared case-insensitively) or a [`MessageEntityKind::TextMention`] of
+    /// the bot user. It may be anywhere in the text, and there may be several
+    /// of them.
+    ///
+    /// Passes [`BotMention`] forwards, with the text of the message with all
+    /// mentions of the bot removed.
+    ///
+    /// ## Dependency requirements
+    ///
+    ///  - [`crate::types::Message`]
+    ///  - [`crate::types::Me`]
+    #[must_use]
+    fn filter_mention_of_bot(self) -> Self;
+
+    /// Returns a handler that accepts the data of a callback query parsed into
+    /// `T`.
+    ///
+    /// Callback queries without data or with data that fails to parse are
+    /// skipped.
+    ///
+    /// ## Dependency requirements
+    ///
+    ///  - [`crate::types::CallbackQuery`]
+    #[must_use]
+    fn filter_callback_query_data<T>(self) -> Self
+    where
+        T: FromStr + Send + Sync + 'static;
+
+    /// Returns a handler that accepts updates from private chats.
+    ///
+    /// Updates without a chat (e.g., inline queries) are skipped.
+    ///
+    /// ## Dependency requirements
+    ///
+    ///  - [`crate::types::Update`]
+    #[must_use]
+    fn chat_is_private(self) -> Self;
+
+    /// Returns a handler that accepts updates from groups (but not
+    /// supergroups).
+    ///
+    /// Updates without a chat (e.g., inline queries) are skipped.
+    ///
+    /// ## Dependency requirements
+    ///
+    ///  - [`crate::types::Update`]
+    #[must_use]
+    fn chat_is_group(self) -> Self;
+
+    /// Returns a handler that accepts updates from supergroups.
+    ///
+    /// Updates without a chat (e.g., inline queries) are skipped.
+    ///
+    /// ## Dependency requirements
+    ///
+    ///  - [`crate::types::Update`]
+    #[must_use]
+    fn chat_is_supergroup(self) -> Self;
+
+    /// Returns a handler that accepts updates from channels.
+    ///
+    /// Updates without a chat (e.g., inline queries) are skipped.
+    ///
+    /// ## Dependency requirements
+    ///
+    ///  - [`crate::types::Update`]
+    #[must_use]
+    fn chat_is_channel(self) -> Self;
+
+    /// Returns a handler that accepts updates only from the specified users.
+    ///
+    /// The user of an update is determined by [`Update::user`]. Updates
+    /// without a user (e.g., channel posts) are skipped.
+    ///
+    /// ## Dependency requirements
+    ///
+    ///  - [`crate::types::Update`]
+    #[must_use]
+    fn filter_users<I>(self, user_ids: I) -> Self
+    where
+        I: IntoIterator<Item = UserId>;
+
+    /// Returns a handler that skips updates from the specified users.
+    ///
+    /// The user of an update is determined by [`Update::user`]. Updates
+    /// without a user (e.g., channel posts) are accepted.
+    ///
+    /// ## Dependency requirements
+    ///
+    ///  - [`crate::types::Update`]
+    #[must_use]
+    fn filter_not_users<I>(self, user_ids: I) -> Self
+    where
+        I: IntoIterator<Item = UserId>;
+
+    /// Returns a handler that continues the chain only if `predicate` returns
+    /// `true`, and otherwise executes `on_fail` instead.
+    ///
+    /// See [`guard_or_reply`].
+    #[must_use]
+    fn guard_or_reply<P, PArgs, F, FArgs>(self, predicate: P, on_fail: F) -> Self
+    where
+        P: Injectable<DependencyMap, bool, PArgs> + Send + Sync + 'static,
+        F: Injectable<DependencyMap, Output, FArgs> + Send + Sync + 'static;
+
+    /// Returns a handler that skips updates that have already been seen.
+    ///
+    /// Telegram may deliver the same update more than once, e.g. when a webhook
+    /// response times out, or when several update listeners are running by
+    /// mistake. This handler remembers the IDs of the last `window` updates and
+    /// doesn't execute the rest of the chain for an update with one of them,
+    /// which makes e.g. payment handlers safe against double processing.
+    ///
+    /// Place it at the beginning of the handler tree, so that all branches are
+    /// protected by a single set of IDs.
+    ///
+    /// ## Panics
+    ///
+    /// If `window` is 0.
+    ///
+    /// ## Dependency requirements
+    ///
+    ///  - [`crate::types::Update`]
+    #[must_use]
+    fn deduplicate_updates(self, window: usize) -> Self;
+
+    /// Returns a handler that skips updates from users who send more than
+    /// `max_updates` updates within `period`.
+    ///
+    /// A user's updates are accepted again as soon as less than `max_updates`
+    /// of their accepted updates are within the last `period`. Updates without
+    /// a user (e.g., channel posts) are always accepted.
+    ///
+    /// Place it at the beginning of the handler tree, so that a flooding user
+    /// is limited in all branches at once.
+    ///
+    /// See also [`HandlerExt::flood_control_with`].
+    ///
+    /// ## Panics
+    ///
+    /// If `max_updates` is 0.
+    ///
+    /// ## Dependency requirements
+    ///
+    ///  - [`crate::types::Update`]
+    #[must_use]
+    fn flood_control(self, max_updates: usize, period: Duration) -> Self;
+
+    /// The same as [`HandlerExt::flood_control`], but executes `on_flood` for
+    /// the first skipped update of a flood, e.g. to ask a user to slow down.
+    ///
+    /// See [`flood_control_with`].
+    #[must_use]
+    fn flood_control_with<F, FArgs>(
+        self,
+        max_updates: usize,
+        period: Duration,
+        on_flood: F,
+    ) -> Self
+    where
+        F: Injectable<DependencyMap, Output, FArgs> + Send + Sync + 'static;
+
     /// Passes [`Dialogue<D, S>`] and `D` as handler dependencies.
     ///
     /// It does so by the following steps:
@@ -52,6 +250,32 @@ pub trait HandlerExt<Output> {
         D: Default + Send + Sync + 'static,
         Upd: GetChatId + Clone + Send + Sync + 'static;
 
+    /// The same as [`HandlerExt::enter_dialogue`], but identifies the dialogue
+    /// of an update by `key` instead of [`GetChatId::chat_id`].
+    ///
+    /// For example, pass [`dialogue::chat_user_key`] to have a separate
+    /// dialogue for each user in a group chat. See
+    /// [`dialogue::enter_with_key`] for how keys interact with the
+    /// distribution of updates by [`Dispatcher`].
+    ///
+    /// ## Dependency requirements
+    ///
+    ///  - `Arc<S>`
+    ///  - `Upd`
+    ///
+    /// [`dialogue::chat_user_key`]: super::dialogue::chat_user_key
+    /// [`dialogue::enter_with_key`]: super::dialogue::enter_with_key
+    /// [`Dispatcher`]: super::Dispatcher
+    #[must_use]
+    fn enter_dialogue_with_key<Upd, S, D, K, F>(self, key: F) -> Self
+    where
+        S: Storage<D, K> + ?Sized + Send + Sync + 'static,
+        <S as Storage<D, K>>::Error: Debug + Send,
+        D: Default + Send + Sync + 'static,
+        Upd: Clone + Send + Sync + 'static,
+        K: Clone + Send + Sync + 'static,
+        F: Fn(&Upd) -> Option<K> + Send + Sync + 'static;
+
     #[must_use]
     #[deprecated(note = "Use the teloxide::handler! API")]
     #[allow(deprecated)]
@@ -71,6 +295,77 @@ where
         self.chain(filter_command::<C, Output>())
     }
 
+    fn filter_command_with_error<C>(self) -> Self
+    where
+        C: BotCommands + Send + Sync + 'static,
+    {
+        self.chain(filter_command_with_error::<C, Output>())
+    }
+
+    fn filter_mention_of_bot(self) -> Self {
+        self.chain(filter_mention_of_bot::<Output>())
+    }
+
+    fn filter_callback_query_data<T>(self) -> Self
+    where
+        T: FromStr + Send + Sync + 'static,
+    {
+        self.chain(filter_callback_query_data::<T, Output>())
+    }
+
+    fn chat_is_private(self) -> Self {
+        self.chain(filter_chat(Chat::is_private))
+    }
+
+    fn chat_is_group(self) -> Self {
+        self.chain(filter_chat(Chat::is_group))
+    }
+
+    fn chat_is_supergroup(self) -> Self {
+        self.chain(filter_chat(Chat::is_supergroup))
+    }
+
+    fn chat_is_channel(self) -> Self {
+        self.chain(filter_chat(Chat::is_channel))
+    }
+
+    fn filter_users<I>(self, user_ids: I) -> Self
+    where
+        I: IntoIterator<Item = UserId>,
+    {
+        self.chain(filter_users(user_ids))
+    }
+
+    fn filter_not_users<I>(self, user_ids: I) -> Self
+    where
+        I: IntoIterator<Item = UserId>,
+    {
+        self.chain(filter_not_users(user_ids))
+    }
+
+    fn guard_or_reply<P, PArgs, F, FArgs>(self, predicate: P, on_fail: F) -> Self
+    where
+        P: Injectable<DependencyMap, bool, PArgs> + Send + Sync + 'static,
+        F: Injectable<DependencyMap, Output, FArgs> + Send + Sync + 'static,
+    {
+        self.chain(guard_or_reply(predicate, on_fail))
+    }
+
+    fn deduplicate_updates(self, window: usize) -> Self {
+        self.chain(deduplicate_updates(window))
+    }
+
+    fn flood_control(self, max_updates: usize, period: Duration) -> Self {
+        self.chain(flood_control(max_updates, period))
+    }
+
+    fn flood_control_with<F, FArgs>(self, max_updates: usize, period: Duration, on_flood: F) -> Self
+    where
+        F: Injectable<DependencyMap, Output, FArgs> + Send + Sync + 'static,
+    {
+        self.chain(flood_control_with(max_updates, period, on_flood))
+    }
+
     fn enter_dialogue<Upd, S, D>(self) -> Self
     where
         S: Storage<D> + ?Sized + Send + Sync + 'static,
@@ -81,6 +376,18 @@ where
         self.chain(super::dialogue::enter::<Upd, S, D, Output>())
     }
 
+    fn enter_dialogue_with_key<Upd, S, D, K, F>(self, key: F) -> Self
+    where
+        S: Storage<D, K> + ?Sized + Send + Sync + 'static,
+        <S as Storage<D, K>>::Error: Debug + Send,
+        D: Default + Send + Sync + 'static,
+        Upd: Clone + Send + Sync + 'static,
+        K: Clone + Send + Sync + 'static,
+        F: Fn(&Upd) -> Option<K> + Send + Sync + 'static,
+    {
+        self.chain(super::dialogue::enter_with_key::<Upd, S, D, Output, K, F>(key))
+    }
+
     #[allow(deprecated)]
     fn dispatch_by<F>(self) -> Self
     where
@@ -107,6 +414,788 @@ where
 {
     dptree::entry().chain(dptree::filter_map(move |message: Message, me: Me| {
         let bot_name = me.user.username.expect("Bots must have a username");
-        message.text().and_then(|text| C::parse(text, bot_name).ok())
+        message.text().and_then(|text| C::parse_with_bot_name(text, bot_name).ok())
+    }))
+}
+
+/// Returns a handler that accepts the result of parsing a command `C`.
+///
+/// A call to this function is the same as
+/// `dptree::entry().filter_command_with_error()`.
+///
+/// See [`HandlerExt::filter_command_with_error`].
+///
+/// ## Dependency requirements
+///
+///  - [`crate::types::Message`]
+///  - [`crate::types::Me`]
+pub fn filter_command_with_error<C, Output>(
+) -> Handler<'static, DependencyMap, Output, DpHandlerDescription>
+where
+    C: BotCommands + Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    dptree::entry().chain(dptree::filter_map(move |message: Message, me: Me| {
+        let bot_name = me.user.username.expect("Bots must have a username");
+        let text = message.text()?;
+        if !command::has_command_prefix::<C>(text) {
+            return None;
+        }
+
+        match C::parse_with_bot_name(text, bot_name) {
+            Ok(command) => Some(Ok(command)),
+            Err(ParseError::WrongBotName(_)) => None,
+            Err(err) => Some(Err(Arc::new(err))),
+        }
+    }))
+}
+
+/// Returns a handler that accepts the data of a callback query parsed into
+/// `T`.
+///
+/// A call to this function is the same as
+/// `dptree::entry().filter_callback_query_data()`.
+///
+/// See [`HandlerExt::filter_callback_query_data`].
+///
+/// ## Dependency requirements
+///
+///  - [`crate::types::CallbackQuery`]
+pub fn filter_callback_query_data<T, Output>(
+) -> Handler<'static, DependencyMap, Output, DpHandlerDescription>
+where
+    T: FromStr + Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    dptree::entry().chain(dptree::filter_map(move |query: CallbackQuery| {
+        query.data.as_deref().and_then(|data| data.parse::<T>().ok())
+    }))
+}
+
+fn filter_chat<Output>(
+    predicate: fn(&Chat) -> bool,
+) -> Handler<'static, DependencyMap, Output, DpHandlerDescription>
+where
+    Output: Send + Sync + 'static,
+{
+    dptree::filter(move |update: Update| update.chat().map_or(false, predicate))
+}
+
+/// Returns a handler that accepts updates only from the specified users.
+///
+/// A call to this function is the same as
+/// `dptree::entry().filter_users(user_ids)`.
+///
+/// See [`HandlerExt::filter_users`].
+///
+/// ## Dependency requirements
+///
+///  - [`crate::types::Update`]
+pub fn filter_users<I, Output>(
+    user_ids: I,
+) -> Handler<'static, DependencyMap, Output, DpHandlerDescription>
+where
+    I: IntoIterator<Item = UserId>,
+    Output: Send + Sync + 'static,
+{
+    let user_ids: HashSet<_> = user_ids.into_iter().collect();
+    dptree::filter(move |update: Update| {
+        update.user().map_or(false, |user| user_ids.contains(&user.id))
+    })
+}
+
+/// Returns a handler that skips updates from the specified users.
+///
+/// A call to this function is the same as
+/// `dptree::entry().filter_not_users(user_ids)`.
+///
+/// See [`HandlerExt::filter_not_users`].
+///
+/// ## Dependency requirements
+///
+///  - [`crate::types::Update`]
+pub fn filter_not_users<I, Output>(
+    user_ids: I,
+) -> Handler<'static, DependencyMap, Output, DpHandlerDescription>
+where
+    I: IntoIterator<Item = UserId>,
+    Output: Send + Sync + 'static,
+{
+    let user_ids: HashSet<_> = user_ids.into_iter().collect();
+    dptree::filter(move |update: Update| {
+        update.user().map_or(true, |user| !user_ids.contains(&user.id))
+    })
+}
+
+/// Returns a handler that continues the chain only if `predicate` returns
+/// `true`, and otherwise executes `on_fail` instead.
+///
+/// This is the canonical way to gate a part of the handler tree: unlike a mere
+/// [`dptree::filter_async`], which silently skips an update, `on_fail` can tell
+/// a user why it was rejected. Both `predicate` and `on_fail` are async
+/// functions that can take any dependencies, like endpoints. The result of
+/// `on_fail` is the result of the handler, so the rest of the tree is not
+/// executed.
+///
+/// A call to this function is the same as
+/// `dptree::entry().guard_or_reply(predicate, on_fail)`.
+///
+/// ## Example
+///
+/// Only users subscribed to a channel can use the bot:
+///
+/// ```no_run
+/// use teloxide::{dispatching::guard_or_reply, prelude::*, types::ChatId};
+///
+/// const CHANNEL_ID: ChatId = ChatId(-1001234567890);
+///
+/// async fn is_subscribed(bot: AutoSend<Bot>, message: Message) -> bool {
+///     let user = match message.from() {
+///         Some(user) => user,
+///         None => return false,
+///     };
+///
+///     match bot.get_chat_member(CHANNEL_ID, user.id).await {
+///         Ok(member) => member.is_present(),
+///         Err(_) => false,
+///     }
+/// }
+///
+/// async fn ask_to_subscribe(bot: AutoSend<Bot>, message: Message) -> ResponseResult<()> {
+///     bot.send_message(message.chat.id, "Please join our channel first.").await?;
+///     Ok(())
+/// }
+///
+/// let handler = Update::filter_message()
+///     .chain(guard_or_reply(is_subscribed, ask_to_subscribe))
+///     .endpoint(|bot: AutoSend<Bot>, message: Message| async move {
+///         bot.send_message(message.chat.id, "Welcome!").await?;
+///         respond(())
+///     });
+/// # let _ = handler;
+/// ```
+pub fn guard_or_reply<P, PArgs, F, FArgs, Output>(
+    predicate: P,
+    on_fail: F,
+) -> Handler<'static, DependencyMap, Output, DpHandlerDescription>
+where
+    P: Injectable<DependencyMap, bool, PArgs> + Send + Sync + 'static,
+    F: Injectable<DependencyMap, Output, FArgs> + Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    let predicate = Arc::new(predicate);
+    let on_fail = Arc::new(on_fail);
+
+    dptree::from_fn_with_description(DpHandlerDescription::entry(), move |deps, cont| {
+        let predicate = Arc::clone(&predicate);
+        let on_fail = Arc::clone(&on_fail);
+
+        async move {
+            let passed = predicate.inject(&deps)().await;
+            if passed {
+                cont(deps).await
+            } else {
+                ControlFlow::Break(on_fail.inject(&deps)().await)
+            }
+        }
+    })
+}
+
+/// Returns a handler that skips updates that have already been seen.
+///
+/// A call to this function is the same as
+/// `dptree::entry().deduplicate_updates(window)`.
+///
+/// See [`HandlerExt::deduplicate_updates`].
+///
+/// ## Panics
+///
+/// If `window` is 0.
+///
+/// ## Dependency requirements
+///
+///  - [`crate::types::Update`]
+pub fn deduplicate_updates<Output>(
+    window: usize,
+) -> Handler<'static, DependencyMap, Output, DpHandlerDescription>
+where
+    Output: Send + Sync + 'static,
+{
+    assert_ne!(window, 0, "A deduplication window must be positive");
+
+    let recent = Arc::new(Mutex::new(RecentIds::new(window)));
+    dptree::filter(move |update: Update| {
+        let is_new = recent.lock().unwrap().insert(update.id);
+        if !is_new {
+            log::warn!("Skipping the duplicate update #{}", update.id);
+        }
+
+        is_new
+    })
+}
+
+/// Returns a handler that skips updates from users who send updates too often.
+///
+/// A call to this function is the same as
+/// `dptree::entry().flood_control(max_updates, period)`.
+///
+/// See [`HandlerExt::flood_control`].
+///
+/// ## Panics
+///
+/// If `max_updates` is 0.
+///
+/// ## Dependency requirements
+///
+///  - [`crate::types::Update`]
+pub fn flood_control<Output>(
+    max_updates: usize,
+    period: Duration,
+) -> Handler<'static, DependencyMap, Output, DpHandlerDescription>
+where
+    Output: Send + Sync + 'static,
+{
+    let limiter = Mutex::new(FloodLimiter::new(max_updates, period));
+    dptree::filter(move |update: Update| check_flood(&limiter, &update) == Flood::No)
+}
+
+/// Returns a handler that skips updates from users who send updates too often,
+/// executing `on_flood` for the first skipped update of a flood.
+///
+/// A call to this function is the same as
+/// `dptree::entry().flood_control_with(max_updates, period, on_flood)`.
+///
+/// See [`HandlerExt::flood_control`] for how updates are limited. The rest of
+/// the chain is not executed for the update that `on_flood` is executed for.
+///
+/// ## Panics
+///
+/// If `max_updates` is 0.
+///
+/// ## Dependency requirements
+///
+///  - [`crate::types::Update`]
+///
+/// ## Example
+///
+/// ```
+/// use std::time::Duration;
+/// use teloxide::{dispatching::flood_control_with, prelude::*};
+///
+/// async fn slow_down(bot: AutoSend<Bot>, message: Message) -> ResponseResult<()> {
+///     bot.send_message(message.chat.id, "Slow down, please.").await?;
+///     Ok(())
+/// }
+///
+/// let handler = Update::filter_message()
+///     .chain(flood_control_with(3, Duration::from_secs(1), slow_down))
+///     .endpoint(|bot: AutoSend<Bot>, message: Message| async move {
+///         bot.send_message(message.chat.id, "Hello!").await?;
+///         respond(())
+///     });
+/// # let _ = handler;
+/// ```
+pub fn flood_control_with<F, FArgs, Output>(
+    max_updates: usize,
+    period: Duration,
+    on_flood: F,
+) -> Handler<'static, DependencyMap, Output, DpHandlerDescription>
+where
+    F: Injectable<DependencyMap, Output, FArgs> + Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    let limiter = Arc::new(Mutex::new(FloodLimiter::new(max_updates, period)));
+    let on_flood = Arc::new(on_flood);
+
+    dptree::from_fn_with_description(DpHandlerDescription::entry(), move |deps, cont| {
+        let limiter = Arc::clone(&limiter);
+        let on_flood = Arc::clone(&on_flood);
+
+        async move {
+            let update: Arc<Update> = deps.get();
+            match check_flood(&limiter, &update) {
+                Flood::No => cont(deps).await,
+                Flood::Started => ControlFlow::Break(on_flood.inject(&deps)().await),
+                Flood::Continues => ControlFlow::Continue(deps),
+            }
+        }
+    })
+}
+
+fn check_flood(limiter: &Mutex<FloodLimiter>, update: &Update) -> Flood {
+    let user_id = match update.user() {
+        Some(user) => user.id,
+        None => return Flood::No,
+    };
+
+    let flood = limiter.lock().unwrap().check(user_id, Instant::now());
+    if flood == Flood::Started {
+        log::warn!("The user #{} sends updates too often, skipping them", user_id);
+    }
+
+    flood
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Flood {
+    No,
+    /// The first update that exceeds the limit.
+    Started,
+    Continues,
+}
+
+/// The times of recently accepted updates of each user.
+struct FloodLimiter {
+    max_updates: usize,
+    period: Duration,
+    users: HashMap<UserId, UserUpdates>,
+    last_cleanup: Instant,
+}
+
+#[derive(Default)]
+struct UserUpdates {
+    // From the oldest to the newest one.
+    accepted: VecDeque<Instant>,
+    flooding: bool,
+}
+
+impl FloodLimiter {
+    fn new(max_updates: usize, period: Duration) -> Self {
+        assert_ne!(max_updates, 0, "The maximum number of updates must be positive");
+
+        Self { max_updates, period, users: HashMap::new(), last_cleanup: Instant::now() }
+    }
+
+    /// Registers an update from `user_id` received at `now`.
+    fn check(&mut self, user_id: UserId, now: Instant) -> Flood {
+        let period = self.period;
+        let is_recent = |time: Instant| now.saturating_duration_since(time) < period;
+
+        // Forgets users who haven't sent anything recently, so that the map
+        // doesn't grow indefinitely.
+        if !is_recent(self.last_cleanup) {
+            self.users
+                .retain(|_, user| user.accepted.back().map_or(false, |&time| is_recent(time)));
+            self.last_cleanup = now;
+        }
+
+        let user = self.users.entry(user_id).or_default();
+        while user.accepted.front().map_or(false, |&time| !is_recent(time)) {
+            user.accepted.pop_front();
+        }
+
+        if user.accepted.len() < self.max_updates {
+            user.accepted.push_back(now);
+            user.flooding = false;
+            Flood::No
+        } else if user.flooding {
+            Flood::Continues
+        } else {
+            user.flooding = true;
+            Flood::Started
+        }
+    }
+}
+
+/// A bounded set of the most recently inserted update IDs.
+struct RecentIds {
+    capacity: usize,
+    ids: HashSet<i32>,
+    // The same IDs as in `ids`, from the oldest to the newest one.
+    order: VecDeque<i32>,
+}
+
+impl RecentIds {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ids: HashSet::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Inserts `id`, forgetting the oldest one if the set is full. Returns
+    /// `false` if `id` is already in the set.
+    fn insert(&mut self, id: i32) -> bool {
+        if !self.ids.insert(id) {
+            return false;
+        }
+
+        if self.order.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        self.order.push_back(id);
+
+        true
+    }
+}
+
+/// A mention of the bot, passed forwards by
+/// [`HandlerExt::filter_mention_of_bot`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BotMention {
+    /// The username of the bot (without `@`).
+    pub bot_username: String,
+
+    /// The text of the message with all mentions of the bot removed.
+    pub text: String,
+}
+
+/// Returns a handler that accepts text messages which mention the bot.
+///
+/// A call to this function is the same as
+/// `dptree::entry().filter_mention_of_bot()`.
+///
+/// See [`HandlerExt::filter_mention_of_bot`].
+///
+/// ## Dependency requirements
+///
+///  - [`crate::types::Message`]
+///  - [`crate::types::Me`]
+pub fn filter_mention_of_bot<Output>(
+) -> Handler<'static, DependencyMap, Output, DpHandlerDescription>
+where
+    Output: Send + Sync + 'static,
+{
+    dptree::entry().chain(dptree::filter_map(move |message: Message, me: Me| {
+        let text = message.text()?;
+        let bot_username = me.user.username.clone().expect("Bots must have a username");
+
+        let mut mentions = message
+            .entities()?
+            .iter()
+            .filter_map(|entity| {
+                let range = utf16_range_to_bytes(text, entity.offset, entity.length)?;
+                let is_bot = match &entity.kind {
+                    MessageEntityKind::Mention => text[range.clone()]
+                        .strip_prefix('@')
+                        .map_or(false, |username| username.eq_ignore_ascii_case(&bot_username)),
+                    MessageEntityKind::TextMention { user } => user.id == me.user.id,
+                    _ => false,
+                };
+
+                is_bot.then(|| range)
+            })
+            .collect::<Vec<_>>();
+
+        if mentions.is_empty() {
+            return None;
+        }
+        mentions.sort_by_key(|range| range.start);
+
+        let mut rest = String::with_capacity(text.len());
+        let mut end_of_previous = 0;
+        for range in mentions {
+            rest.push_str(&text[end_of_previous..range.start]);
+            end_of_previous = range.end;
+        }
+        rest.push_str(&text[end_of_previous..]);
+
+        Some(BotMention { bot_username, text: rest.trim().to_owned() })
     }))
 }
+
+/// Converts a range of an entity, which is measured in UTF-16 code units, to a
+/// range of bytes in `text`.
+///
+/// Returns `None` if the range is out of bounds or doesn't lie on char
+/// boundaries.
+fn utf16_range_to_bytes(text: &str, offset: usize, length: usize) -> Option<Range<usize>> {
+    let mut start = None;
+    let mut utf16_pos = 0;
+
+    for (byte_pos, c) in text.char_indices().chain([(text.len(), '\0')]) {
+        if utf16_pos == offset {
+            start = Some(byte_pos);
+        }
+        if utf16_pos == offset + length {
+            return start.map(|start| start..byte_pos);
+        }
+        if utf16_pos > offset + length {
+            break;
+        }
+
+        utf16_pos += c.len_utf16();
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::ControlFlow;
+
+    use std::time::{Duration, Instant};
+
+    use serde_json::{json, Value};
+
+    use super::{
+        guard_or_reply, utf16_range_to_bytes, BotMention, Flood, FloodLimiter, HandlerExt,
+        RecentIds,
+    };
+    use crate::{
+        dispatching::{DpHandlerDescription, UpdateFilterExt},
+        types::{Me, Update, UpdateKind, UserId},
+    };
+
+    type Handler = dptree::Handler<'static, dptree::di::DependencyMap, (), DpHandlerDescription>;
+
+    fn entry() -> Handler {
+        dptree::entry()
+    }
+
+    /// Deserializes an update with a single `field`, e.g. `message`.
+    fn update(field: &str, value: Value) -> Update {
+        let mut update = json!({ "update_id": 1 });
+        update[field] = value;
+
+        let update: Update = serde_json::from_value(update).unwrap();
+        assert!(!matches!(update.kind, UpdateKind::Error(_)), "{:?}", update.kind);
+        update
+    }
+
+    fn user(id: u64) -> Value {
+        json!({ "id": id, "is_bot": false, "first_name": "User" })
+    }
+
+    fn private_chat() -> Value {
+        json!({ "id": 1, "type": "private", "first_name": "User" })
+    }
+
+    fn message(chat: Value, text: &str) -> Value {
+        json!({ "message_id": 1, "date": 0, "chat": chat, "from": user(1), "text": text })
+    }
+
+    fn me() -> Me {
+        serde_json::from_value(json!({
+            "id": 42,
+            "is_bot": true,
+            "first_name": "Bot",
+            "username": "my_bot",
+            "can_join_groups": true,
+            "can_read_all_group_messages": false,
+            "supports_inline_queries": false,
+        }))
+        .unwrap()
+    }
+
+    /// Checks which of `handlers` accept which of `updates`: the `i`th handler
+    /// must accept only the `i`th update.
+    async fn assert_accepts(handlers: &[Handler], updates: &[Update]) {
+        for (i, handler) in handlers.iter().enumerate() {
+            for (j, update) in updates.iter().enumerate() {
+                let accepted = matches!(
+                    handler.dispatch(dptree::deps![update.clone()]).await,
+                    ControlFlow::Break(())
+                );
+                assert_eq!(accepted, i == j, "handler #{}, update #{}", i, j);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn chat_type() {
+        let group = json!({ "id": -1, "type": "group", "title": "Group" });
+        let supergroup = json!({ "id": -1001, "type": "supergroup", "title": "Supergroup" });
+        let channel = json!({ "id": -1002, "type": "channel", "title": "Channel" });
+
+        let handlers = [
+            entry().chat_is_private(),
+            entry().chat_is_group(),
+            entry().chat_is_supergroup(),
+            entry().chat_is_channel(),
+        ]
+        .map(|handler| handler.endpoint(|| async {}));
+        let updates = [
+            update("message", message(private_chat(), "hi")),
+            update("message", message(group, "hi")),
+            update("message", message(supergroup, "hi")),
+            update(
+                "channel_post",
+                json!({ "message_id": 1, "date": 0, "chat": channel, "text": "hi" }),
+            ),
+            // Updates without a chat are skipped.
+            update(
+                "inline_query",
+                json!({ "id": "1", "from": user(1), "query": "", "offset": "" }),
+            ),
+        ];
+
+        assert_accepts(&handlers, &updates).await;
+    }
+
+    #[tokio::test]
+    async fn callback_query_data() {
+        let handler = Update::filter_callback_query()
+            .filter_callback_query_data::<u8>()
+            .endpoint(|data: u8| async move { data });
+        let dispatch = |data: Option<&str>| {
+            let mut query = json!({ "id": "1", "from": user(1), "chat_instance": "1" });
+            if let Some(data) = data {
+                query["data"] = data.into();
+            }
+            handler.dispatch(dptree::deps![update("callback_query", query)])
+        };
+
+        assert!(matches!(dispatch(Some("42")).await, ControlFlow::Break(42)));
+        assert!(matches!(dispatch(Some("256")).await, ControlFlow::Continue(_)));
+        assert!(matches!(dispatch(Some("many")).await, ControlFlow::Continue(_)));
+        assert!(matches!(dispatch(None).await, ControlFlow::Continue(_)));
+    }
+
+    #[tokio::test]
+    async fn users() {
+        let mut from_bob = message(private_chat(), "hi");
+        from_bob["from"] = user(2);
+        let channel = json!({ "id": -1002, "type": "channel", "title": "Channel" });
+        let updates = [
+            update("message", message(private_chat(), "hi")),
+            update("message", from_bob),
+            // Channel posts have no user.
+            update(
+                "channel_post",
+                json!({ "message_id": 1, "date": 0, "chat": channel, "text": "hi" }),
+            ),
+        ];
+
+        let accepted = |handler: Handler| {
+            let handler = handler.endpoint(|| async {});
+            let updates = updates.clone();
+            async move {
+                let mut accepted = Vec::new();
+                for (i, update) in updates.into_iter().enumerate() {
+                    if let ControlFlow::Break(()) = handler.dispatch(dptree::deps![update]).await {
+                        accepted.push(i);
+                    }
+                }
+                accepted
+            }
+        };
+
+        assert_eq!(accepted(entry().filter_users([UserId(1)])).await, [0]);
+        assert_eq!(accepted(entry().filter_users([UserId(2), UserId(3)])).await, [1]);
+        assert_eq!(accepted(entry().filter_not_users([UserId(1)])).await, [1, 2]);
+        assert_eq!(accepted(entry().filter_not_users([])).await, [0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn mention_of_bot() {
+        let handler = Update::filter_message()
+            .filter_mention_of_bot()
+            .endpoint(|mention: BotMention| async move { mention.text });
+        let dispatch = |message| handler.dispatch(dptree::deps![update("message", message), me()]);
+
+        let mut mention = message(private_chat(), "@My_Bot hi");
+        mention["entities"] = json!([{ "type": "mention", "offset": 0, "length": 7 }]);
+        assert!(matches!(dispatch(mention).await, ControlFlow::Break(text) if text == "hi"));
+
+        let mut other = message(private_chat(), "@other_bot hi");
+        other["entities"] = json!([{ "type": "mention", "offset": 0, "length": 10 }]);
+        assert!(matches!(dispatch(other).await, ControlFlow::Continue(_)));
+
+        let bot_user = json!({ "id": 42, "is_bot": true, "first_name": "Bot" });
+        let mut text_mention = message(private_chat(), "hi Bot");
+        text_mention["entities"] =
+            json!([{ "type": "text_mention", "offset": 3, "length": 3, "user": bot_user }]);
+        assert!(matches!(dispatch(text_mention).await, ControlFlow::Break(text) if text == "hi"));
+
+        let mut other_text_mention = message(private_chat(), "hi User");
+        other_text_mention["entities"] =
+            json!([{ "type": "text_mention", "offset": 3, "length": 4, "user": user(1) }]);
+        assert!(matches!(dispatch(other_text_mention).await, ControlFlow::Continue(_)));
+
+        // Only the mentions of the bot are removed.
+        let mut several = message(private_chat(), "@my_bot hi @other_bot and @MY_BOT bye");
+        several["entities"] = json!([
+            { "type": "mention", "offset": 0, "length": 7 },
+            { "type": "mention", "offset": 11, "length": 10 },
+            { "type": "mention", "offset": 26, "length": 7 },
+        ]);
+        assert!(matches!(
+            dispatch(several).await,
+            ControlFlow::Break(text) if text == "hi @other_bot and  bye"
+        ));
+
+        // The offset is measured in UTF-16 code units, "👋" is 2 of them.
+        let mut non_bmp = message(private_chat(), "👋 привет, @my_bot");
+        non_bmp["entities"] = json!([{ "type": "mention", "offset": 11, "length": 7 }]);
+        assert!(matches!(
+            dispatch(non_bmp).await,
+            ControlFlow::Break(text) if text == "👋 привет,"
+        ));
+
+        assert!(matches!(
+            dispatch(message(private_chat(), "my_bot hi")).await,
+            ControlFlow::Continue(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn guard() {
+        let handler = guard_or_reply(|x: i32| async move { x > 0 }, || async { "rejected" })
+            .endpoint(|| async { "accepted" });
+
+        assert!(matches!(handler.dispatch(dptree::deps![1]).await, ControlFlow::Break("accepted")));
+        assert!(matches!(
+            handler.dispatch(dptree::deps![-1]).await,
+            ControlFlow::Break("rejected")
+        ));
+    }
+
+    #[test]
+    fn flood_limiter() {
+        let second = Duration::from_secs(1);
+        let mut limiter = FloodLimiter::new(2, second);
+        let start = Instant::now();
+        let (alice, bob) = (UserId(1), UserId(2));
+
+        assert_eq!(limiter.check(alice, start), Flood::No);
+        assert_eq!(limiter.check(alice, start), Flood::No);
+        assert_eq!(limiter.check(alice, start), Flood::Started);
+        assert_eq!(limiter.check(alice, start), Flood::Continues);
+        assert_eq!(limiter.check(bob, start), Flood::No);
+
+        // Skipped updates don't prolong the flood.
+        assert_eq!(limiter.check(alice, start + second), Flood::No);
+        assert_eq!(limiter.check(alice, start + second), Flood::No);
+        assert_eq!(limiter.check(alice, start + second), Flood::Started);
+
+        // Users who have stopped sending updates are forgotten.
+        assert_eq!(limiter.check(bob, start + 3 * second), Flood::No);
+        assert_eq!(limiter.users.len(), 1);
+    }
+
+    #[test]
+    fn recent_ids() {
+        let mut recent = RecentIds::new(2);
+        assert!(recent.insert(1));
+        assert!(recent.insert(2));
+        assert!(!recent.insert(1));
+        assert!(!recent.insert(2));
+
+        // #1 is forgotten.
+        assert!(recent.insert(3));
+        assert!(recent.insert(1));
+        assert!(!recent.insert(3));
+        assert!(recent.insert(2));
+    }
+
+    #[test]
+    fn utf16_ranges() {
+        let text = "hi @bot";
+        assert_eq!(utf16_range_to_bytes(text, 3, 4).map(|r| &text[r]), Some("@bot"));
+
+        // "👋" is 2 UTF-16 code units and 4 bytes long.
+        let text = "👋 @bot, привет";
+        assert_eq!(utf16_range_to_bytes(text, 3, 4).map(|r| &text[r]), Some("@bot"));
+        assert_eq!(utf16_range_to_bytes(text, 9, 6).map(|r| &text[r]), Some("привет"));
+        assert_eq!(utf16_range_to_bytes(text, 0, 2).map(|r| &text[r]), Some("👋"));
+
+        // In the middle of a surrogate pair.
+        assert_eq!(utf16_range_to_bytes(text, 1, 2), None);
+        // Out of bounds.
+        assert_eq!(utf16_range_to_bytes(text, 9, 7), None);
+    }
+}
@@ -113,6 +113,11 @@ pub use dispatcher::{Dispatcher, DispatcherBuilder, UpdateHandler};
 pub use distribution::DefaultKey;
 pub use filter_ext::{MessageFilterExt, UpdateFilterExt};
 pub use handler_description::DpHandlerDescription;
-pub use handler_ext::{filter_command, HandlerExt};
+pub use handler_ext::{
+    deduplicate_updates, filter_callback_query_data, filter_command, filter_command_with_error,
+    filter_mention_of_bot, filter_not_users, filter_users, flood_control, flood_control_with,
+    guard_or_reply, BotMention, HandlerExt,
+};
 #[allow(deprecated)]
 pub use handler_factory::HandlerFactory;
+pub use tokio_util::sync::CancellationToken;
@@ -7,6 +7,8 @@
 //!   listener.
 //! - [`polling`] function, which returns a long polling listener with your
 //!   configuration.
+//! - [`PollingBuilder`], which allows to control the `offset` of a long polling
+//!   listener.
 //! - Various functions in the [`webhooks`] module that return webhook listeners
 //!
 //! And then you can extract updates from it or pass them directly to a
@@ -43,7 +45,7 @@ mod polling;
 mod stateful_listener;
 
 pub use self::{
-    polling::{polling, polling_default},
+    polling::{polling, polling_default, PollingBuilder},
     stateful_listener::StatefulListener,
 };
 
@@ -1,4 +1,8 @@
-use std::{convert::TryInto, time::Duration};
+use std::{
+    convert::TryInto,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use futures::{
     future::{ready, Either},
@@ -37,7 +41,8 @@ where
 /// - `bot`: Using this bot, the returned update listener will receive updates.
 /// - `timeout`: A timeout in seconds for polling.
 /// - `limit`: Limits the number of updates to be retrieved at once. Values
-///   between 1—100 are accepted.
+///   between 1—100 are accepted, other values are clamped into this range (see
+///   [`PollingBuilder::limit`]).
 /// - `allowed_updates`: A list the types of updates you want to receive.
 ///
 /// See [`GetUpdates`] for defaults.
@@ -46,11 +51,13 @@ where
 ///
 /// ## Notes
 ///
-/// - `timeout` should not be bigger than http client timeout, see
-///   [`default_reqwest_settings`] for default http client settings.
+/// - `timeout` should be smaller than http client timeout, see
+///   [`default_reqwest_settings`] for default http client settings. It is
+///   adjusted like in [`PollingBuilder::timeout`].
 /// - [`repl`]s and [`Dispatcher`] use [`hint_allowed_updates`] to set
 ///   `allowed_updates`, so you rarely need to pass `allowed_updates`
-///   explicitly.
+///   explicitly. If you do, the explicitly passed `allowed_updates` take
+///   precedence over the hint.
 ///
 /// [`default_reqwest_settings`]: teloxide::net::default_reqwest_settings
 /// [`repl`]: fn@crate::repl
@@ -133,93 +140,363 @@ where
     R: Requester + Send + 'static,
     <R as Requester>::GetUpdates: Send,
 {
-    struct State<B: Requester> {
-        bot: B,
-        timeout: Option<u32>,
-        limit: Option<u8>,
-        allowed_updates: Option<Vec<AllowedUpdate>>,
-        offset: i32,
-        flag: AsyncStopFlag,
-        token: AsyncStopToken,
-        force_stop: bool,
+    let mut builder = PollingBuilder::new(bot);
+    if let Some(timeout) = timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(limit) = limit {
+        builder = builder.limit(limit);
+    }
+    if let Some(allowed_updates) = allowed_updates {
+        builder = builder.allowed_updates(allowed_updates);
+    }
+
+    builder.build()
+}
+
+/// The request timeout of the http client created by
+/// [`default_reqwest_settings`].
+///
+/// [`default_reqwest_settings`]: crate::net::default_reqwest_settings
+// Keep in sync with `teloxide_core::net::default_reqwest_settings`.
+const DEFAULT_CLIENT_TIMEOUT: Duration = Duration::from_secs(17);
+
+/// A builder of a long polling update listener.
+///
+/// Unlike [`polling`](polling()), it allows to control the `offset` that is
+/// used to request updates, e.g. to persist it externally and resume exactly
+/// where the bot left off after a restart.
+///
+/// ## Example
+///
+/// ```no_run
+/// use teloxide::{dispatching::update_listeners::PollingBuilder, prelude::*};
+///
+/// # fn load_offset() -> i32 { 0 }
+/// # fn save_offset(_: i32) {}
+/// let bot = Bot::from_env().auto_send();
+/// let listener = PollingBuilder::new(bot)
+///     .starting_offset(load_offset())
+///     .on_offset_advance(save_offset)
+///     .build();
+/// # let _ = listener;
+/// ```
+pub struct PollingBuilder<R> {
+    bot: R,
+    timeout: Option<Duration>,
+    limit: Option<u8>,
+    allowed_updates: Option<Vec<AllowedUpdate>>,
+    offset: i32,
+    on_offset_advance: Option<Box<dyn FnMut(i32) + Send>>,
+    delete_webhook_on_conflict: bool,
+    client_timeout: Duration,
+}
+
+impl<R> PollingBuilder<R>
+where
+    R: Requester + Send + 'static,
+    <R as Requester>::GetUpdates: Send,
+{
+    /// Constructs a new builder with the default settings.
+    ///
+    /// See [`GetUpdates`] for defaults.
+    pub fn new(bot: R) -> Self {
+        Self {
+            bot,
+            timeout: None,
+            limit: None,
+            allowed_updates: None,
+            offset: 0,
+            on_offset_advance: None,
+            delete_webhook_on_conflict: false,
+            client_timeout: DEFAULT_CLIENT_TIMEOUT,
+        }
+    }
+
+    /// Sets a timeout for polling.
+    ///
+    /// A lower timeout makes the listener react to a stop signal faster, a
+    /// higher one reduces the number of requests on low-traffic bots.
+    ///
+    /// It must be smaller than http client timeout (see
+    /// [`PollingBuilder::client_timeout`]), otherwise long polling requests
+    /// fail spuriously. If it is not, it is reduced to one second less than
+    /// the client timeout, and a warning is logged.
+    #[must_use]
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self { timeout: Some(timeout), ..self }
+    }
+
+    /// Sets the request timeout of the http client of the bot, which
+    /// [`PollingBuilder::timeout`] is adjusted to.
+    ///
+    /// By default, it is 17 seconds, the timeout of the client created by
+    /// [`default_reqwest_settings`]. Set it if the bot uses a client with a
+    /// different timeout, e.g. to use a bigger polling timeout.
+    ///
+    /// [`default_reqwest_settings`]: crate::net::default_reqwest_settings
+    #[must_use]
+    pub fn client_timeout(self, client_timeout: Duration) -> Self {
+        Self { client_timeout, ..self }
     }
 
-    fn stream<B>(st: &mut State<B>) -> impl Stream<Item = Result<Update, B::Err>> + Send + '_
+    /// Limits the number of updates to be retrieved at once.
+    ///
+    /// A bigger limit reduces the number of requests on high-traffic bots.
+    ///
+    /// Telegram accepts only values in the range 1—100. If `limit` is not in
+    /// this range, it is clamped into it, and a warning is logged.
+    #[must_use]
+    pub fn limit(self, limit: u8) -> Self {
+        let clamped = limit.clamp(1, 100);
+        if clamped != limit {
+            log::warn!(
+                "Invalid limit of updates: {} (expected 1—100), using {} instead",
+                limit,
+                clamped
+            );
+        }
+
+        Self { limit: Some(clamped), ..self }
+    }
+
+    /// Sets a list of the types of updates you want to receive.
+    ///
+    /// [`Dispatcher`] and [`repl`]s derive `allowed_updates` from the handler
+    /// via [`hint_allowed_updates`], so you rarely need to set them. If you do,
+    /// the list passed here takes precedence over the hint, and a warning is
+    /// logged if the handler may need update kinds missing from the list.
+    ///
+    /// [`Dispatcher`]: crate::dispatching::Dispatcher
+    /// [`repl`]: fn@crate::repl
+    /// [`hint_allowed_updates`]:
+    /// super::UpdateListener::hint_allowed_updates
+    #[must_use]
+    pub fn allowed_updates(self, allowed_updates: Vec<AllowedUpdate>) -> Self {
+        Self { allowed_updates: Some(allowed_updates), ..self }
+    }
+
+    /// Sets the `offset` of the first [`get_updates`] request.
+    ///
+    /// Telegram forgets all updates with ids less than `offset`, so this is
+    /// usually a value previously reported by
+    /// [`PollingBuilder::on_offset_advance`].
+    ///
+    /// By default, it is 0, i.e. the listener starts with the oldest
+    /// unconfirmed update.
+    ///
+    /// [`get_updates`]: crate::requests::Requester::get_updates
+    #[must_use]
+    pub fn starting_offset(self, offset: i32) -> Self {
+        Self { offset, ..self }
+    }
+
+    /// Sets a callback which is called with the new `offset` each time the
+    /// listener advances it, i.e. when it yields an update.
+    ///
+    /// The new `offset` is the id of the yielded update + one. The callback is
+    /// called as the consumer of the listener (e.g., [`Dispatcher`]) pulls
+    /// each update, so the updates of a received batch that the consumer
+    /// hasn't pulled yet are not skipped after a restart with this `offset`.
+    ///
+    /// [`Dispatcher`]: crate::dispatching::Dispatcher
+    #[must_use]
+    pub fn on_offset_advance<F>(self, callback: F) -> Self
     where
-        B: Requester + Send,
-        <B as Requester>::GetUpdates: Send,
+        F: FnMut(i32) + Send + 'static,
     {
-        stream::unfold(st, move |state| async move {
-            let State { timeout, limit, allowed_updates, bot, offset, flag, force_stop, .. } =
-                &mut *state;
+        Self { on_offset_advance: Some(Box::new(callback)), ..self }
+    }
+
+    /// Makes the listener delete a webhook (logging a warning) if it prevents
+    /// receiving updates.
+    ///
+    /// Telegram doesn't allow to receive updates via long polling while a
+    /// webhook is set up, so [`get_updates`] requests fail. Regardless of this
+    /// setting, when a request fails, the listener checks whether a webhook is
+    /// set up and, if so, logs an error explaining the failure. With this
+    /// setting, it deletes the webhook instead and continues polling.
+    ///
+    /// [`get_updates`]: crate::requests::Requester::get_updates
+    #[must_use]
+    pub fn delete_webhook_on_conflict(self) -> Self {
+        Self { delete_webhook_on_conflict: true, ..self }
+    }
 
-            if *force_stop {
-                return None;
+    /// Returns a long polling update listener with the configuration of this
+    /// builder.
+    ///
+    /// See [`polling`](polling()) for how it works.
+    pub fn build(self) -> impl UpdateListener<R::Err> {
+        let Self {
+            bot,
+            timeout,
+            limit,
+            allowed_updates,
+            offset,
+            on_offset_advance,
+            delete_webhook_on_conflict,
+            client_timeout,
+        } = self;
+        let (token, flag) = AsyncStopToken::new_pair();
+
+        let max_timeout = client_timeout.saturating_sub(Duration::from_secs(1));
+        let timeout = timeout.map(|timeout| {
+            if timeout <= max_timeout {
+                return timeout;
             }
 
-            if flag.is_stopped() {
-                let mut req = bot.get_updates().offset(*offset).timeout(0).limit(1);
-                req.payload_mut().allowed_updates = allowed_updates.take();
+            log::warn!(
+                "Long polling timeout ({:?}) is not smaller than the http client timeout ({:?}), \
+                 using {:?} instead; set `PollingBuilder::client_timeout` if the client has a \
+                 bigger timeout",
+                timeout,
+                client_timeout,
+                max_timeout
+            );
+            max_timeout
+        });
+
+        let state = State {
+            bot,
+            timeout: timeout.map(|t| t.as_secs().try_into().expect("timeout is too big")),
+            limit,
+            allowed_updates,
+            offset,
+            on_offset_advance: on_offset_advance.map(|callback| Arc::new(Mutex::new(callback))),
+            delete_webhook_on_conflict,
+            flag,
+            token,
+            force_stop: false,
+        };
 
-                return match req.send().await {
-                    Ok(_) => None,
-                    Err(err) => {
-                        // Prevents infinite retries, see https://github.com/teloxide/teloxide/issues/496
-                        *force_stop = true;
+        let stop_token = |st: &mut State<_>| st.token.clone();
 
-                        Some((Either::Left(stream::once(ready(Err(err)))), state))
+        let hint_allowed_updates =
+            Some(|state: &mut State<_>, allowed: &mut dyn Iterator<Item = AllowedUpdate>| {
+                match &state.allowed_updates {
+                    // Explicitly set allowed updates take precedence over the hint.
+                    Some(explicit) => {
+                        let missing =
+                            allowed.filter(|kind| !explicit.contains(kind)).collect::<Vec<_>>();
+                        if !missing.is_empty() {
+                            log::warn!(
+                                "The handler may need {:?} updates, but they are not in the \
+                                 explicitly set `allowed_updates` ({:?})",
+                                missing,
+                                explicit
+                            );
+                        }
                     }
-                };
-            }
+                    None => state.allowed_updates = Some(allowed.collect()),
+                }
+            });
+        let timeout_hint = Some(move |_: &State<_>| timeout);
+
+        StatefulListener::new_with_hints(
+            state,
+            stream,
+            stop_token,
+            hint_allowed_updates,
+            timeout_hint,
+        )
+    }
+}
+
+// Shared with the stream of updates of a batch, which reports the offset of
+// each update when it is yielded.
+type OffsetCallback = Arc<Mutex<Box<dyn FnMut(i32) + Send>>>;
+
+struct State<B: Requester> {
+    bot: B,
+    timeout: Option<u32>,
+    limit: Option<u8>,
+    allowed_updates: Option<Vec<AllowedUpdate>>,
+    offset: i32,
+    on_offset_advance: Option<OffsetCallback>,
+    delete_webhook_on_conflict: bool,
+    flag: AsyncStopFlag,
+    token: AsyncStopToken,
+    force_stop: bool,
+}
 
-            let mut req = bot.get_updates();
-            *req.payload_mut() = GetUpdates {
-                offset: Some(*offset),
-                timeout: *timeout,
-                limit: *limit,
-                allowed_updates: allowed_updates.take(),
+fn stream<B>(st: &mut State<B>) -> impl Stream<Item = Result<Update, B::Err>> + Send + '_
+where
+    B: Requester + Send,
+    <B as Requester>::GetUpdates: Send,
+{
+    stream::unfold(st, move |state| async move {
+        let State {
+            timeout,
+            limit,
+            allowed_updates,
+            bot,
+            offset,
+            on_offset_advance,
+            delete_webhook_on_conflict,
+            flag,
+            force_stop,
+            ..
+        } = &mut *state;
+
+        if *force_stop {
+            return None;
+        }
+
+        if flag.is_stopped() {
+            let mut req = bot.get_updates().offset(*offset).timeout(0).limit(1);
+            req.payload_mut().allowed_updates = allowed_updates.take();
+
+            return match req.send().await {
+                Ok(_) => None,
+                Err(err) => {
+                    // Prevents infinite retries, see https://github.com/teloxide/teloxide/issues/496
+                    *force_stop = true;
+
+                    Some((Either::Left(stream::once(ready(Err(err)))), state))
+                }
             };
+        }
 
-            match req.send().await {
-                Ok(updates) => {
-                    // Set offset to the last update's id + 1
-                    if let Some(upd) = updates.last() {
-                        *offset = upd.id + 1;
-                    }
+        let mut req = bot.get_updates();
+        *req.payload_mut() = GetUpdates {
+            offset: Some(*offset),
+            timeout: *timeout,
+            limit: *limit,
+            allowed_updates: allowed_updates.take(),
+        };
 
-                    let updates = updates.into_iter().map(Ok);
-                    Some((Either::Right(stream::iter(updates)), state))
+        let updates = match req.send().await {
+            Ok(updates) => updates,
+            Err(err) => {
+                if !is_webhook_conflict(&err)
+                    || !resolve_webhook_conflict(bot, *delete_webhook_on_conflict).await
+                {
+                    return Some((Either::Left(stream::once(ready(Err(err)))), state));
                 }
-                Err(err) => Some((Either::Left(stream::once(ready(Err(err)))), state)),
+
+                // The webhook is deleted, the next request should succeed.
+                Vec::new()
             }
-        })
-        .flatten()
-    }
+        };
 
-    let (token, flag) = AsyncStopToken::new_pair();
-
-    let state = State {
-        bot,
-        timeout: timeout.map(|t| t.as_secs().try_into().expect("timeout is too big")),
-        limit,
-        allowed_updates,
-        offset: 0,
-        flag,
-        token,
-        force_stop: false,
-    };
+        // Set offset to the last update's id + 1
+        if let Some(upd) = updates.last() {
+            *offset = upd.id + 1;
+        }
 
-    let stop_token = |st: &mut State<_>| st.token.clone();
+        let on_offset_advance = on_offset_advance.clone();
+        let updates = stream::iter(updates).map(move |upd| {
+            if let Some(on_offset_advance) = &on_offset_advance {
+                let mut on_offset_advance = on_offset_advance.lock().unwrap();
+                on_offset_advance(upd.id + 1);
+            }
 
-    let hint_allowed_updates =
-        Some(|state: &mut State<_>, allowed: &mut dyn Iterator<Item = AllowedUpdate>| {
-            // TODO: we should probably warn if there already were different allowed updates
-            // before
-            state.allowed_updates = Some(allowed.collect());
+            Ok(upd)
         });
-    let timeout_hint = Some(move |_: &State<_>| timeout);
-
-    StatefulListener::new_with_hints(state, stream, stop_token, hint_allowed_updates, timeout_hint)
+        Some((Either::Right(updates), state))
+    })
+    .flatten()
 }
 
 async fn delete_webhook_if_setup<R>(requester: &R)
@@ -243,6 +520,110 @@ where
     }
 }
 
+/// Checks whether `get_updates` has failed because of a webhook, i.e. Telegram
+/// has returned a conflict error: either "can't use getUpdates method while
+/// webhook is active" or "terminated by setWebhook request".
+///
+/// Other errors (e.g. network errors or [`RetryAfter`]) are not caused by a
+/// webhook, so there is no need to check it with `get_webhook_info`.
+///
+/// [`RetryAfter`]: crate::RequestError::RetryAfter
+fn is_webhook_conflict<E>(err: &E) -> bool
+where
+    E: std::error::Error,
+{
+    let err = err.to_string();
+    err.contains("Conflict") && (err.contains("webhook") || err.contains("setWebhook"))
+}
+
+/// Checks whether a webhook is set up after a [conflict] and, if so, either
+/// deletes it (if `delete` is true) or logs an error.
+///
+/// Returns `true` if the webhook has been deleted.
+///
+/// [conflict]: is_webhook_conflict
+// `requester` is `&mut`, so that the future is `Send` without `R: Sync`.
+async fn resolve_webhook_conflict<R>(requester: &mut R, delete: bool) -> bool
+where
+    R: Requester,
+{
+    let get_webhook_info = requester.get_webhook_info().send();
+    let url = match get_webhook_info.await {
+        Ok(webhook_info) => match webhook_info.url {
+            Some(url) => url,
+            None => return false,
+        },
+        // The failure is probably not caused by a webhook, e.g. the network is down.
+        Err(_) => return false,
+    };
+
+    if !delete {
+        log::error!(
+            "Cannot receive updates via long polling while a webhook is set up ({}), delete it \
+             via `delete_webhook` or use `PollingBuilder::delete_webhook_on_conflict`",
+            url
+        );
+        return false;
+    }
+
+    log::warn!("Deleting a webhook ({}) to receive updates via long polling", url);
+    let delete_webhook = requester.delete_webhook().send();
+    match delete_webhook.await {
+        Ok(_) => true,
+        Err(err) => {
+            log::error!("Failed to delete a webhook: {:?}", err);
+            false
+        }
+    }
+}
+
+#[test]
+fn timeout_is_smaller_than_client_timeout() {
+    let timeout_hint = |builder: PollingBuilder<crate::Bot>| builder.build().timeout_hint();
+    let builder = || PollingBuilder::new(crate::Bot::new("TOKEN"));
+
+    assert_eq!(timeout_hint(builder()), None);
+    assert_eq!(
+        timeout_hint(builder().timeout(Duration::from_secs(10))),
+        Some(Duration::from_secs(10))
+    );
+    assert_eq!(
+        timeout_hint(builder().timeout(Duration::from_secs(60))),
+        Some(Duration::from_secs(16))
+    );
+    assert_eq!(
+        timeout_hint(
+            builder().timeout(Duration::from_secs(60)).client_timeout(Duration::from_secs(90))
+        ),
+        Some(Duration::from_secs(60))
+    );
+}
+
+#[test]
+fn limit_is_clamped() {
+    let limit = |limit| PollingBuilder::new(crate::Bot::new("TOKEN")).limit(limit).limit;
+
+    assert_eq!(limit(0), Some(1));
+    assert_eq!(limit(1), Some(1));
+    assert_eq!(limit(100), Some(100));
+    assert_eq!(limit(101), Some(100));
+    assert_eq!(limit(u8::MAX), Some(100));
+}
+
+#[test]
+fn webhook_conflict() {
+    use std::io;
+
+    let err = |text| io::Error::new(io::ErrorKind::Other, text);
+
+    assert!(is_webhook_conflict(&err("Conflict: can't use getUpdates method while webhook is \
+                                      active; use deleteWebhook to delete the webhook first")));
+    assert!(is_webhook_conflict(&err("Conflict: terminated by setWebhook request")));
+    assert!(!is_webhook_conflict(&err("Conflict: terminated by other getUpdates request")));
+    assert!(!is_webhook_conflict(&err("Retry after 5s")));
+    assert!(!is_webhook_conflict(&err("error sending request for url (https://api.telegram.org)")));
+}
+
 #[test]
 fn polling_is_send() {
     use crate::dispatching::update_listeners::AsUpdateStream;
@@ -256,3 +637,21 @@ fn polling_is_send() {
 
     fn assert_send(_: &impl Send) {}
 }
+
+#[test]
+fn polling_builder_is_send() {
+    use crate::dispatching::update_listeners::AsUpdateStream;
+
+    let bot = crate::Bot::new("TOKEN");
+    let mut polling = PollingBuilder::new(bot)
+        .starting_offset(42)
+        .on_offset_advance(|_| {})
+        .delete_webhook_on_conflict()
+        .build();
+
+    assert_send(&polling);
+    assert_send(&polling.as_stream());
+    assert_send(&polling.stop_token());
+
+    fn assert_send(_: &impl Send) {}
+}
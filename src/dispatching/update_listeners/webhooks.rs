@@ -42,13 +42,40 @@ pub struct Options {
     ///
     /// Default - false.
     pub drop_pending_updates: bool,
+
+    /// A secret token that Telegram sends in the
+    /// `X-Telegram-Bot-Api-Secret-Token` header of every webhook request.
+    ///
+    /// When it is set, requests with a missing or mismatched header are
+    /// rejected with `401 Unauthorized`, so malicious actors that know the
+    /// [url][Options::url] can't send fake updates to your bot.
+    ///
+    /// **Note**: the `set_webhook` request of the currently used
+    /// `teloxide-core` version doesn't support this parameter, so [`axum`] and
+    /// [`axum_to_router`] can't send it to Telegram. If it is set, they log a
+    /// warning and don't call `set_webhook` at all, so you need to register
+    /// the webhook with the token yourself (or use [`axum_no_setup`]).
+    ///
+    /// Default - None.
+    ///
+    /// [`axum`]: self::axum()
+    /// [`axum_to_router`]: self::axum_to_router
+    /// [`axum_no_setup`]: self::axum_no_setup
+    pub secret_token: Option<String>,
 }
 
 impl Options {
     /// Construct a new webhook options, see [`Options::address`] and
     /// [`Options::url`] for details.
     pub fn new(address: SocketAddr, url: url::Url) -> Self {
-        Self { address, url, certificate: None, max_connections: None, drop_pending_updates: false }
+        Self {
+            address,
+            url,
+            certificate: None,
+            max_connections: None,
+            drop_pending_updates: false,
+            secret_token: None,
+        }
     }
 
     /// Upload your public key certificate so that the root certificate in use
@@ -71,6 +98,28 @@ impl Options {
     pub fn drop_pending_updates(self) -> Self {
         Self { drop_pending_updates: true, ..self }
     }
+
+    /// Rejects webhook requests that don't have the
+    /// `X-Telegram-Bot-Api-Secret-Token` header equal to `token`, see
+    /// [`Options::secret_token`] for details.
+    ///
+    /// ## Panics
+    ///
+    /// If `token` is not 1-256 characters long or contains characters other
+    /// than `A-Z`, `a-z`, `0-9`, `_` and `-` (these are the only ones
+    /// [allowed by Telegram][set_webhook]).
+    ///
+    /// [set_webhook]: https://core.telegram.org/bots/api#setwebhook
+    pub fn secret_token(self, token: String) -> Self {
+        assert!(
+            (1..=256).contains(&token.len())
+                && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+            "Invalid secret token: {:?}",
+            token
+        );
+
+        Self { secret_token: Some(token), ..self }
+    }
 }
 
 #[cfg(feature = "webhooks-axum")]
@@ -84,6 +133,10 @@ mod axum;
 /// Calls `set_webhook` with arguments from `options`.
 ///
 /// Note: this takes out `certificate`.
+///
+/// If `options.secret_token` is set, this only logs a warning, because
+/// `set_webhook` can't send the token to Telegram yet, and then all webhook
+/// requests would be rejected.
 async fn setup_webhook<R>(bot: R, options: &mut Options) -> Result<(), R::Err>
 where
     R: Requester,
@@ -91,6 +144,14 @@ where
     use crate::requests::Request;
     use teloxide_core::requests::HasPayload;
 
+    if options.secret_token.is_some() {
+        log::warn!(
+            "`set_webhook` can't send `secret_token` to Telegram yet, not setting up the webhook; \
+             register it with the secret token yourself"
+        );
+        return Ok(());
+    }
+
     let &mut Options {
         ref url, ref mut certificate, max_connections, drop_pending_updates, ..
     } = options;
@@ -105,6 +166,14 @@ where
     Ok(())
 }
 
+/// Compares a secret token with a value of the
+/// `X-Telegram-Bot-Api-Secret-Token` header in constant time (with respect to
+/// the contents of the token).
+fn secret_token_matches(expected: &str, actual: &[u8]) -> bool {
+    expected.len() == actual.len()
+        && expected.bytes().zip(actual).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
 /// Returns first (`.0`) field from a tuple as a `&mut` reference.
 ///
 /// This hack is needed because there isn't currently a way to easily force a
@@ -118,3 +187,29 @@ where
 fn tuple_first_mut<A, B>(tuple: &mut (A, B)) -> &mut A {
     &mut tuple.0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{secret_token_matches, setup_webhook, Options};
+    use crate::Bot;
+
+    #[test]
+    fn secret_token() {
+        assert!(secret_token_matches("secret", b"secret"));
+        assert!(!secret_token_matches("secret", b"secret_"));
+        assert!(!secret_token_matches("secret", b"secrex"));
+        assert!(!secret_token_matches("secret", b""));
+    }
+
+    #[tokio::test]
+    async fn setup_webhook_with_secret_token() {
+        let mut options = Options::new(
+            ([127, 0, 0, 1], 8443).into(),
+            "https://example.com/webhook".parse().unwrap(),
+        )
+        .secret_token("secret".to_owned());
+
+        // `set_webhook` is not sent, so the invalid token doesn't matter.
+        setup_webhook(Bot::new("TOKEN"), &mut options).await.unwrap();
+    }
+}
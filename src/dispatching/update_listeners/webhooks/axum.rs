@@ -4,7 +4,7 @@ use crate::{
     dispatching::{
         stop_token::{AsyncStopFlag, StopToken},
         update_listeners::{
-            webhooks::{setup_webhook, tuple_first_mut, Options},
+            webhooks::{secret_token_matches, setup_webhook, tuple_first_mut, Options},
             UpdateListener,
         },
     },
@@ -14,7 +14,8 @@ use crate::{
 /// Webhook implementation based on the [mod@axum] framework.
 ///
 /// This function does all the work necessary for webhook to work, it:
-/// - Calls [`set_webhook`], so telegram starts sending updates our way
+/// - Calls [`set_webhook`], so telegram starts sending updates our way (unless
+///   [`Options::secret_token`] is set, see its documentation)
 /// - Spawns [mod@axum] server listening for updates
 /// - When the update listener is [`stop`]ped, calls [`delete_webhook`]
 ///
@@ -65,7 +66,8 @@ where
 /// existing [mod@axum] server.
 ///
 /// This function does most of the work necessary for webhook to work, it:
-/// - Calls [`set_webhook`], so telegram starts sending updates our way
+/// - Calls [`set_webhook`], so telegram starts sending updates our way (unless
+///   [`Options::secret_token`] is set, see its documentation)
 /// - When the update listener is [`stop`]ped, calls [`delete_webhook`]
 ///
 /// The only missing part is running [mod@axum] server with a returned
@@ -154,7 +156,12 @@ pub fn axum_no_setup(
         dispatching::{stop_token::AsyncStopToken, update_listeners},
         types::Update,
     };
-    use axum::{extract::Extension, http::StatusCode, response::IntoResponse, routing::post};
+    use axum::{
+        extract::Extension,
+        http::{HeaderMap, StatusCode},
+        response::IntoResponse,
+        routing::post,
+    };
     use tokio::sync::mpsc;
     use tokio_stream::wrappers::UnboundedReceiverStream;
     use tower::ServiceBuilder;
@@ -166,10 +173,19 @@ pub fn axum_no_setup(
     let (tx, rx): (Sender, _) = mpsc::unbounded_channel();
 
     async fn telegram_request(
+        headers: HeaderMap,
         input: String,
+        secret: Extension<SecretToken>,
         tx: Extension<CSender>,
         flag: Extension<AsyncStopFlag>,
     ) -> impl IntoResponse {
+        if let SecretToken(Some(secret)) = &*secret {
+            let header = headers.get("X-Telegram-Bot-Api-Secret-Token");
+            if !header.map_or(false, |header| secret_token_matches(secret, header.as_bytes())) {
+                return StatusCode::UNAUTHORIZED;
+            }
+        }
+
         let tx = match tx.get() {
             None => return StatusCode::SERVICE_UNAVAILABLE,
             // Do not process updates after `.stop()` is called even if the server is still
@@ -204,6 +220,7 @@ pub fn axum_no_setup(
     let app = axum::Router::new().route(options.url.path(), post(telegram_request)).layer(
         ServiceBuilder::new()
             .layer(TraceLayer::new_for_http())
+            .layer(Extension(SecretToken(options.secret_token.map(Into::into))))
             .layer(Extension(ClosableSender::new(tx)))
             .layer(Extension(stop_flag.clone()))
             .into_inner(),
@@ -221,6 +238,10 @@ pub fn axum_no_setup(
     (listener, stop_flag, app)
 }
 
+/// A secret token from [`Options::secret_token`], if any.
+#[derive(Clone)]
+struct SecretToken(Option<std::sync::Arc<str>>);
+
 /// A terrible workaround to drop axum extension
 struct ClosableSender<T> {
     origin: std::sync::Arc<std::sync::RwLock<Option<tokio::sync::mpsc::UnboundedSender<T>>>>,
@@ -245,3 +266,39 @@ impl<T> ClosableSender<T> {
         self.origin.write().unwrap().take();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use tower::Service;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn secret_token() {
+        let options = Options::new(
+            ([127, 0, 0, 1], 8443).into(),
+            "https://example.com/webhook".parse().unwrap(),
+        )
+        .secret_token("secret".to_owned());
+        let (_listener, _stop_flag, mut app) = axum_no_setup(options);
+
+        let mut status = |token: Option<&str>| {
+            let mut request = Request::builder().method("POST").uri("/webhook");
+            if let Some(token) = token {
+                request = request.header("X-Telegram-Bot-Api-Secret-Token", token);
+            }
+            let request = request.body(Body::from("{}")).unwrap();
+
+            let response = app.call(request);
+            async move { response.await.unwrap().status() }
+        };
+
+        assert_eq!(status(None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(Some("secrex")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(Some("secret")).await, StatusCode::OK);
+    }
+}
@@ -0,0 +1,68 @@
+//! Keeping a chat action (e.g. "typing…") visible while a handler runs.
+
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+
+use crate::{
+    requests::{Request, Requester},
+    types::{ChatAction, ChatId},
+};
+
+/// How often a chat action is resent.
+///
+/// Telegram clients show an action for 5 seconds (or less, if a message from
+/// the bot arrives).
+const RESEND_PERIOD: Duration = Duration::from_secs(4);
+
+/// Sends `action` to `chat_id` and keeps resending it until the returned guard
+/// is dropped.
+///
+/// Use it around slow operations, so that a user keeps seeing e.g. "typing…"
+/// until the reply is ready. Errors of sending the action are logged and
+/// otherwise ignored.
+///
+/// ## Example
+///
+/// ```no_run
+/// use teloxide::{prelude::*, types::ChatAction, utils::chat_action};
+///
+/// # async fn slow_reply() -> String { unimplemented!() }
+/// async fn handler(bot: AutoSend<Bot>, message: Message) -> ResponseResult<()> {
+///     let typing = chat_action::keep_alive(bot.clone(), message.chat.id, ChatAction::Typing);
+///     let reply = slow_reply().await;
+///     drop(typing);
+///
+///     bot.send_message(message.chat.id, reply).await?;
+///     Ok(())
+/// }
+/// ```
+pub fn keep_alive<R>(bot: R, chat_id: ChatId, action: ChatAction) -> ChatActionGuard
+where
+    R: Requester + Send + Sync + 'static,
+{
+    let handle = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RESEND_PERIOD);
+        loop {
+            interval.tick().await;
+            if let Err(err) = bot.send_chat_action(chat_id, action).send().await {
+                log::warn!("Cannot send a chat action to #{}: {}", chat_id, err);
+            }
+        }
+    });
+
+    ChatActionGuard { handle }
+}
+
+/// A guard returned from [`keep_alive`], which stops resending a chat action
+/// when dropped.
+#[must_use = "A chat action stops being resent as soon as the guard is dropped"]
+pub struct ChatActionGuard {
+    handle: JoinHandle<()>,
+}
+
+impl Drop for ChatActionGuard {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
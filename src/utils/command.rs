@@ -219,6 +219,44 @@ pub trait BotCommands: Sized {
     where
         N: Into<String>;
 
+    /// Parses a command, comparing the `@bot_username` suffix
+    /// case-insensitively.
+    ///
+    /// Telegram usernames are case-insensitive, so `/start@MyBot` and
+    /// `/start@mybot` are addressed to the same bot. [`BotCommands::parse`]
+    /// requires the suffix to match `bot_username` exactly, while this
+    /// function accepts any ASCII case of it.
+    ///
+    /// ## Example
+    /// ```
+    /// # #[cfg(feature = "macros")] {
+    /// use teloxide::utils::command::BotCommands;
+    ///
+    /// #[derive(BotCommands, PartialEq, Debug)]
+    /// #[command(rename = "lowercase")]
+    /// enum Command {
+    ///     Start,
+    /// }
+    ///
+    /// assert!(Command::parse("/start@mybot", "MyBot").is_err());
+    /// assert_eq!(Command::parse_with_bot_name("/start@mybot", "MyBot").unwrap(), Command::Start);
+    /// # }
+    /// ```
+    fn parse_with_bot_name<N>(s: &str, bot_username: N) -> Result<Self, ParseError>
+    where
+        N: Into<String>,
+    {
+        let bot_username = bot_username.into();
+
+        // The same splitting as the one used by `#[derive(BotCommands)]`.
+        let suffix = s.split(' ').next().and_then(|command| command.split('@').nth(1));
+
+        match suffix {
+            Some(suffix) if suffix.eq_ignore_ascii_case(&bot_username) => Self::parse(s, suffix),
+            _ => Self::parse(s, bot_username),
+        }
+    }
+
     /// Returns descriptions of the commands suitable to be shown to the user
     /// (for example when `/help` command is used).
     fn descriptions() -> CommandDescriptions<'static>;
@@ -339,6 +377,45 @@ impl<'a> CommandDescriptions<'a> {
     pub fn username_from_me(self, me: &'a Me) -> CommandDescriptions<'a> {
         self.username(me.user.username.as_deref().expect("Bots must have usernames"))
     }
+
+    /// Returns the command which is the most similar to `command` (e.g.,
+    /// `/start` for `/strat`), if any is similar enough.
+    ///
+    /// `command` is expected to include a prefix. The similarity is measured
+    /// by the [Levenshtein distance] (counting a transposition of two adjacent
+    /// characters as a single edit), ignoring ASCII case.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use teloxide::utils::command::{CommandDescription, CommandDescriptions};
+    ///
+    /// let descriptions = CommandDescriptions::new(&[
+    ///     CommandDescription { prefix: "/", command: "start", description: "start this bot" },
+    ///     CommandDescription { prefix: "/", command: "help", description: "show this message" },
+    /// ]);
+    ///
+    /// assert_eq!(descriptions.closest("/strat").map(|descr| descr.command), Some("start"));
+    /// assert_eq!(descriptions.closest("/hlep").map(|descr| descr.command), Some("help"));
+    /// assert!(descriptions.closest("/settings").is_none());
+    /// ```
+    ///
+    /// [Levenshtein distance]: https://en.wikipedia.org/wiki/Levenshtein_distance
+    pub fn closest(&self, command: &str) -> Option<&CommandDescription<'a>> {
+        let command = command.to_ascii_lowercase();
+        // The same threshold as the one used by rustc for suggesting names.
+        let max_distance = command.chars().count().max(3) / 3;
+
+        self.descriptions
+            .iter()
+            .map(|descr| {
+                let candidate = format!("{}{}", descr.prefix, descr.command).to_ascii_lowercase();
+                (edit_distance(&candidate, &command), descr)
+            })
+            .filter(|&(distance, _)| distance <= max_distance)
+            .min_by_key(|&(distance, _)| distance)
+            .map(|(_, descr)| descr)
+    }
 }
 
 /// Parses a string into a command with args.
@@ -417,6 +494,267 @@ where
     Some((command, words.collect()))
 }
 
+/// Splits a string into words like a shell does.
+///
+/// Words are separated by whitespace, except for the whitespace inside quotes:
+///  - Inside single quotes (`'`), all characters are preserved literally.
+///  - Inside double quotes (`"`), a backslash (`\`) escapes `"` and `\`, other
+///    characters are preserved literally.
+///  - Outside of quotes, a backslash escapes any character.
+///
+/// It is intended to be used in custom parsers of [`BotCommands`] (see
+/// `#[command(parse_with = "parser")]`), so that a single argument can contain
+/// spaces.
+///
+/// ## Example
+/// ```
+/// # #[cfg(feature = "macros")] {
+/// use teloxide::utils::command::{split_shell_words, BotCommands, ParseError};
+///
+/// fn parse_say(input: String) -> Result<(String, String), ParseError> {
+///     match split_shell_words(&input)?.as_slice() {
+///         [text, volume] => Ok((text.clone(), volume.clone())),
+///         words => Err(ParseError::Custom(
+///             format!("Expected 2 arguments, found {}", words.len()).into(),
+///         )),
+///     }
+/// }
+///
+/// #[derive(BotCommands, PartialEq, Debug)]
+/// #[command(rename = "lowercase")]
+/// enum Command {
+///     #[command(parse_with = "parse_say")]
+///     Say(String, String),
+/// }
+///
+/// let command = Command::parse(r#"/say "hello world" loud"#, "").unwrap();
+/// assert_eq!(command, Command::Say("hello world".to_owned(), "loud".to_owned()));
+/// # }
+/// ```
+///
+/// ## Errors
+///
+/// Returns [`ParseError::IncorrectFormat`] if a quote is not terminated or the
+/// string ends with an unescaped backslash.
+pub fn split_shell_words(s: &str) -> Result<Vec<String>, ParseError> {
+    enum State {
+        Unquoted,
+        SingleQuoted,
+        DoubleQuoted,
+    }
+
+    let mut words = Vec::new();
+    let mut word = String::new();
+    // Whether there is a word being built (it may be empty, e.g. `""`).
+    let mut in_word = false;
+    let mut state = State::Unquoted;
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match state {
+            State::Unquoted => match c {
+                '\'' => {
+                    state = State::SingleQuoted;
+                    in_word = true;
+                }
+                '"' => {
+                    state = State::DoubleQuoted;
+                    in_word = true;
+                }
+                '\\' => {
+                    word.push(chars.next().ok_or_else(unterminated_escape)?);
+                    in_word = true;
+                }
+                c if c.is_whitespace() => {
+                    if in_word {
+                        words.push(std::mem::take(&mut word));
+                        in_word = false;
+                    }
+                }
+                c => {
+                    word.push(c);
+                    in_word = true;
+                }
+            },
+            State::SingleQuoted => match c {
+                '\'' => state = State::Unquoted,
+                c => word.push(c),
+            },
+            State::DoubleQuoted => match c {
+                '"' => state = State::Unquoted,
+                '\\' => match chars.next().ok_or_else(unterminated_quote)? {
+                    c @ ('"' | '\\') => word.push(c),
+                    c => {
+                        word.push('\\');
+                        word.push(c);
+                    }
+                },
+                c => word.push(c),
+            },
+        }
+    }
+
+    match state {
+        State::Unquoted => {
+            if in_word {
+                words.push(word);
+            }
+
+            Ok(words)
+        }
+        State::SingleQuoted | State::DoubleQuoted => Err(unterminated_quote()),
+    }
+}
+
+/// Returns a reply for a user whose `text` has failed to parse as a command `C`
+/// with `error`, or `None` if the user should not be replied.
+///
+/// For an unknown command, the reply suggests the most similar known one (see
+/// [`CommandDescriptions::closest`]) or, if none is similar enough, lists all
+/// the commands. For a known command with invalid arguments, the reply
+/// explains what is wrong and describes the command.
+///
+/// `None` is returned for a text that doesn't start with any prefix of the
+/// commands of `C` (i.e. is not a command at all), and for a command addressed
+/// to another bot ([`ParseError::WrongBotName`]).
+///
+/// ## Example
+///
+/// Use it with [`HandlerExt::filter_command_with_error`] to reply to invalid
+/// commands:
+///
+/// ```no_run
+/// # #[cfg(feature = "macros")] {
+/// use std::sync::Arc;
+/// use teloxide::{
+///     prelude::*,
+///     utils::command::{self, BotCommands, ParseError},
+/// };
+///
+/// #[derive(BotCommands, Clone)]
+/// #[command(rename = "lowercase", description = "These commands are supported:")]
+/// enum Command {
+///     #[command(description = "display this text.")]
+///     Help,
+///     #[command(description = "roll a die with the given number of sides.")]
+///     Roll(u8),
+/// }
+///
+/// async fn answer(
+///     bot: AutoSend<Bot>,
+///     message: Message,
+///     command: Result<Command, Arc<ParseError>>,
+/// ) -> ResponseResult<()> {
+///     let reply = match command {
+///         Ok(Command::Help) => Command::descriptions().to_string(),
+///         Ok(Command::Roll(sides)) => format!("You need a {}-sided die.", sides),
+///         Err(err) => {
+///             match command::describe_parse_error::<Command>(message.text().unwrap(), &err) {
+///                 Some(reply) => reply,
+///                 None => return Ok(()),
+///             }
+///         }
+///     };
+///
+///     bot.send_message(message.chat.id, reply).await?;
+///     Ok(())
+/// }
+///
+/// let handler = Update::filter_message().filter_command_with_error::<Command>().endpoint(answer);
+/// # let _ = handler;
+/// # }
+/// ```
+///
+/// [`HandlerExt::filter_command_with_error`]:
+/// crate::dispatching::HandlerExt::filter_command_with_error
+pub fn describe_parse_error<C>(text: &str, error: &ParseError) -> Option<String>
+where
+    C: BotCommands,
+{
+    if let ParseError::WrongBotName(_) = error {
+        return None;
+    }
+
+    if !has_command_prefix::<C>(text) {
+        return None;
+    }
+
+    let descriptions = C::descriptions();
+    let command = text.split_whitespace().next()?.split('@').next()?;
+
+    let known = descriptions
+        .descriptions
+        .iter()
+        .find(|descr| command.strip_prefix(descr.prefix) == Some(descr.command));
+    let reply = match (error, known) {
+        (ParseError::UnknownCommand(_), _) | (_, None) => match descriptions.closest(command) {
+            Some(closest) => {
+                format!(
+                    "Unknown command {}. Did you mean {}{}?",
+                    command, closest.prefix, closest.command
+                )
+            }
+            None => format!("Unknown command {}.\n\n{}", command, descriptions),
+        },
+        (error, Some(known)) => {
+            format!("{}\n\n{}", error, CommandDescriptions::new(std::slice::from_ref(known)))
+        }
+    };
+
+    Some(reply)
+}
+
+/// Whether `text` starts with a prefix of one of the commands of `C`, i.e. is
+/// meant to be a command at all.
+pub(crate) fn has_command_prefix<C>(text: &str) -> bool
+where
+    C: BotCommands,
+{
+    C::descriptions().descriptions.iter().any(|descr| text.starts_with(descr.prefix))
+}
+
+/// The minimal number of single-character insertions, deletions,
+/// substitutions, or transpositions of two adjacent characters required to
+/// change `a` into `b`.
+///
+/// This is the Levenshtein distance extended with transpositions (the "optimal
+/// string alignment" distance), because swapped characters are a common typo.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // The distances between prefixes of `a` of the two previous lengths and
+    // each prefix of `b`.
+    let mut prev_prev = vec![0; b.len() + 1];
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for i in 1..=a.len() {
+        let mut cur = vec![0; b.len() + 1];
+        cur[0] = i;
+
+        for j in 1..=b.len() {
+            let substitution = prev[j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            cur[j] = substitution.min(prev[j] + 1).min(cur[j - 1] + 1);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                cur[j] = cur[j].min(prev_prev[j - 2] + 1);
+            }
+        }
+
+        prev_prev = std::mem::replace(&mut prev, cur);
+    }
+
+    prev[b.len()]
+}
+
+fn unterminated_quote() -> ParseError {
+    ParseError::IncorrectFormat("unterminated quote".into())
+}
+
+fn unterminated_escape() -> ParseError {
+    ParseError::IncorrectFormat("unterminated escape sequence".into())
+}
+
 impl Display for ParseError {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
         match self {
@@ -500,4 +838,56 @@ mod tests {
         let actual = parse_command(data, "");
         assert_eq!(actual, expected)
     }
+
+    #[test]
+    fn edit_distance_() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("start", "start"), 0);
+        assert_eq!(edit_distance("start", ""), 5);
+        assert_eq!(edit_distance("start", "strat"), 1);
+        assert_eq!(edit_distance("help", "hepl"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("привет", "превед"), 2);
+        // A transposed pair cannot be edited further.
+        assert_eq!(edit_distance("ca", "abc"), 3);
+    }
+
+    #[test]
+    fn closest_command() {
+        let descriptions = CommandDescriptions::new(&[
+            CommandDescription { prefix: "/", command: "start", description: "" },
+            CommandDescription { prefix: "/", command: "stats", description: "" },
+            CommandDescription { prefix: "!", command: "ban", description: "" },
+        ]);
+        let closest = |command| descriptions.closest(command).map(|descr| descr.command);
+
+        assert_eq!(closest("/Start"), Some("start"));
+        assert_eq!(closest("/starts"), Some("start"));
+        assert_eq!(closest("/statz"), Some("stats"));
+        assert_eq!(closest("!bam"), Some("ban"));
+        assert_eq!(closest("/ban"), Some("ban"));
+        assert_eq!(closest("/settings"), None);
+    }
+
+    #[test]
+    fn split_shell_words_() {
+        let split = |s| split_shell_words(s).unwrap();
+
+        assert_eq!(split("  a  b\tc "), vec!["a", "b", "c"]);
+        assert_eq!(split(r#""hello world" loud"#), vec!["hello world", "loud"]);
+        assert_eq!(split(r#"'it "is"' it\'s"#), vec![r#"it "is""#, "it's"]);
+        assert_eq!(split(r#""a \"b\" \\ \c""#), vec![r#"a "b" \ \c"#]);
+        assert_eq!(split(r#"x"y z"'!'"#), vec!["xy z!"]);
+        assert_eq!(split(r#""" ''"#), vec!["", ""]);
+        assert_eq!(split(r"a\ b"), vec!["a b"]);
+        assert_eq!(split(""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn split_shell_words_errors() {
+        assert!(matches!(split_shell_words(r#""abc"#), Err(ParseError::IncorrectFormat(_))));
+        assert!(matches!(split_shell_words("'abc"), Err(ParseError::IncorrectFormat(_))));
+        assert!(matches!(split_shell_words(r#""abc\"#), Err(ParseError::IncorrectFormat(_))));
+        assert!(matches!(split_shell_words("abc\\"), Err(ParseError::IncorrectFormat(_))));
+    }
 }
@@ -38,9 +38,10 @@ pub fn strike(s: &str) -> String {
 
 /// Builds an inline link with an anchor.
 ///
-/// Escapes the passed URL and the link text.
+/// Escapes the passed URL (including `"`, which would end the attribute) and
+/// the link text.
 pub fn link(url: &str, text: &str) -> String {
-    format!("<a href=\"{}\">{}</a>", escape(url), escape(text))
+    format!("<a href=\"{}\">{}</a>", escape(url).replace('"', "&quot;"), escape(text))
 }
 
 /// Builds an inline user mention link with an anchor.
@@ -76,9 +77,23 @@ pub fn code_inline(s: &str) -> String {
 /// Escapes the string to be shown "as is" within the Telegram HTML message
 /// style.
 ///
+/// Use it to interpolate arbitrary (e.g., user-provided) text into a message.
+///
 /// Does not escape ' and " characters (as should be for usual HTML), because
 /// they shoudn't be escaped by the [spec].
 ///
+/// ## Example
+///
+/// ```
+/// use teloxide::utils::html;
+///
+/// let name = "<Tom & Jerry>";
+/// assert_eq!(
+///     format!("Hello, <b>{}</b>!", html::escape(name)),
+///     "Hello, <b>&lt;Tom &amp; Jerry&gt;</b>!"
+/// );
+/// ```
+///
 /// [spec]: https://core.telegram.org/bots/api#html-style
 pub fn escape(s: &str) -> String {
     s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
@@ -91,6 +106,159 @@ pub fn user_mention_or_link(user: &User) -> String {
     }
 }
 
+/// A builder of a whole message in the [HTML][spec] style.
+///
+/// Unlike the functions of this module, it escapes all the passed strings, so
+/// that arbitrary (e.g., user-provided) text cannot break the markup. Nested
+/// entities are built by the `*_with` methods from an inner builder.
+///
+/// ## Example
+///
+/// ```
+/// use teloxide::utils::html;
+///
+/// let name = "<Tom & Jerry>";
+/// let message = html::Builder::new()
+///     .text("Hello, ")
+///     .bold(name)
+///     .text("! Read ")
+///     .link("https://example.com/?page=1&lang=en", "the rules")
+///     .text(".")
+///     .build();
+/// assert_eq!(
+///     message,
+///     "Hello, <b>&lt;Tom &amp; Jerry&gt;</b>! Read \
+///      <a href=\"https://example.com/?page=1&amp;lang=en\">the rules</a>."
+/// );
+/// ```
+///
+/// [spec]: https://core.telegram.org/bots/api#html-style
+#[derive(Clone, Debug, Default)]
+pub struct Builder {
+    message: String,
+}
+
+impl Builder {
+    /// Constructs a builder of an empty message.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends plain text.
+    #[must_use]
+    pub fn text(self, s: &str) -> Self {
+        self.push(&escape(s))
+    }
+
+    /// Appends bold text.
+    #[must_use]
+    pub fn bold(self, s: &str) -> Self {
+        self.push(&bold(&escape(s)))
+    }
+
+    /// Appends italic text.
+    #[must_use]
+    pub fn italic(self, s: &str) -> Self {
+        self.push(&italic(&escape(s)))
+    }
+
+    /// Appends underlined text.
+    #[must_use]
+    pub fn underline(self, s: &str) -> Self {
+        self.push(&underline(&escape(s)))
+    }
+
+    /// Appends strikethrough text.
+    #[must_use]
+    pub fn strike(self, s: &str) -> Self {
+        self.push(&strike(&escape(s)))
+    }
+
+    /// Appends a bold entity with the contents built by `f`.
+    #[must_use]
+    pub fn bold_with<F>(self, f: F) -> Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        let inner = f(Self::new());
+        self.push(&bold(&inner.message))
+    }
+
+    /// Appends an italic entity with the contents built by `f`.
+    #[must_use]
+    pub fn italic_with<F>(self, f: F) -> Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        let inner = f(Self::new());
+        self.push(&italic(&inner.message))
+    }
+
+    /// Appends an underline entity with the contents built by `f`.
+    #[must_use]
+    pub fn underline_with<F>(self, f: F) -> Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        let inner = f(Self::new());
+        self.push(&underline(&inner.message))
+    }
+
+    /// Appends a strikethrough entity with the contents built by `f`.
+    #[must_use]
+    pub fn strike_with<F>(self, f: F) -> Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        let inner = f(Self::new());
+        self.push(&strike(&inner.message))
+    }
+
+    /// Appends an inline link with `text` as an anchor.
+    ///
+    /// Both `url` and `text` are escaped.
+    #[must_use]
+    pub fn link(self, url: &str, text: &str) -> Self {
+        self.push(&link(url, text))
+    }
+
+    /// Appends an inline user mention with `text` as an anchor.
+    #[must_use]
+    pub fn user_mention(self, user_id: i64, text: &str) -> Self {
+        self.link(&format!("tg://user?id={}", user_id), text)
+    }
+
+    /// Appends an inline code.
+    #[must_use]
+    pub fn code(self, s: &str) -> Self {
+        self.push(&code_inline(s))
+    }
+
+    /// Appends a code block.
+    #[must_use]
+    pub fn code_block(self, code: &str) -> Self {
+        self.push(&code_block(code))
+    }
+
+    /// Appends a code block with a specific language syntax.
+    #[must_use]
+    pub fn code_block_with_lang(self, code: &str, lang: &str) -> Self {
+        self.push(&code_block_with_lang(code, lang))
+    }
+
+    /// Returns the built message.
+    #[must_use]
+    pub fn build(self) -> String {
+        self.message
+    }
+
+    fn push(mut self, s: &str) -> Self {
+        self.message.push_str(s);
+        self
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use teloxide_core::types::UserId;
@@ -180,6 +348,20 @@ mod tests {
         );
         assert_eq!(escape("<p>你好 & 再見</p>"), "&lt;p&gt;你好 &amp; 再見&lt;/p&gt;");
         assert_eq!(escape("'foo\""), "'foo\"");
+        // Escaping is not applied twice.
+        assert_eq!(escape("&amp;"), "&amp;amp;");
+    }
+
+    #[test]
+    fn test_escape_every_char() {
+        for (c, escaped) in [('&', "&amp;"), ('<', "&lt;"), ('>', "&gt;")] {
+            assert_eq!(escape(&c.to_string()), escaped);
+        }
+
+        let others = (' '..='~').chain(['\n', 'ё', '👋']).filter(|c| !"&<>".contains(*c));
+        for c in others {
+            assert_eq!(escape(&c.to_string()), c.to_string());
+        }
     }
 
     #[test]
@@ -206,4 +388,26 @@ mod tests {
             r#"<a href="tg://user/?id=123456789">Name</a>"#
         )
     }
+
+    #[test]
+    fn test_builder() {
+        let message = Builder::new()
+            .text("1 < 2 & ")
+            .bold("<b>")
+            .text(" ")
+            .italic_with(|b| b.text("a ").strike("</i>"))
+            .text(" ")
+            .code("x < y")
+            .build();
+        assert_eq!(
+            message,
+            "1 &lt; 2 &amp; <b>&lt;b&gt;</b> <i>a <s>&lt;/i&gt;</s></i> <code>x &lt; y</code>"
+        );
+
+        let message = Builder::new().link("https://example.com/\"><b>", "link").build();
+        assert_eq!(message, "<a href=\"https://example.com/&quot;&gt;&lt;b&gt;\">link</a>");
+
+        let message = Builder::new().user_mention(123, "<Tom>").code_block("<pre>").build();
+        assert_eq!(message, "<a href=\"tg://user?id=123\">&lt;Tom&gt;</a><pre>&lt;pre&gt;</pre>");
+    }
 }
@@ -0,0 +1,149 @@
+//! Sending texts that are too long for a single message.
+
+use crate::{
+    requests::{Request, Requester},
+    types::{ChatId, Message},
+};
+
+/// The maximum length of a message text, in UTF-16 code units.
+pub const MAX_MESSAGE_LENGTH: usize = 4096;
+
+/// Splits `text` into chunks of at most `max_len` UTF-16 code units.
+///
+/// A chunk ends at the last line break that fits into it or, if there is
+/// none, at the last whitespace; the line break (or whitespace) itself is
+/// dropped. Only a single word longer than `max_len` is split in the middle,
+/// and never in the middle of a character. Chunks consisting only of
+/// whitespace are skipped, because Telegram doesn't accept empty messages.
+///
+/// Note that the text is split without regard to markup, so split formatted
+/// texts only if their entities are short enough not to cross chunk borders.
+///
+/// ## Panics
+///
+/// If `max_len` is less than 2, because then a character may not fit into a
+/// chunk.
+///
+/// ## Example
+///
+/// ```
+/// use teloxide::utils::long_text;
+///
+/// assert_eq!(long_text::split("first line\nsecond line", 15), ["first line", "second line"]);
+/// ```
+pub fn split(text: &str, max_len: usize) -> Vec<&str> {
+    assert!(max_len >= 2, "max_len must be at least 2, got {}", max_len);
+
+    let mut chunks = Vec::new();
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let (chunk, next) = match fitting_prefix_len(rest, max_len) {
+            None => (rest, ""),
+            Some(fits) => {
+                let prefix = &rest[..fits];
+                // A separator at the very beginning would result in an empty chunk.
+                let sep = prefix
+                    .rfind('\n')
+                    .filter(|&sep| sep > 0)
+                    .or_else(|| prefix.rfind(char::is_whitespace).filter(|&sep| sep > 0));
+                match sep {
+                    Some(sep) => {
+                        let sep_len = prefix[sep..].chars().next().map_or(0, char::len_utf8);
+                        (&rest[..sep], &rest[sep + sep_len..])
+                    }
+                    None => (prefix, &rest[fits..]),
+                }
+            }
+        };
+
+        if !chunk.trim().is_empty() {
+            chunks.push(chunk);
+        }
+        rest = next;
+    }
+
+    chunks
+}
+
+/// Sends `text` to `chat_id`, split by [`split`] into as many messages as
+/// needed.
+///
+/// The messages are sent sequentially, so they appear in order. Stops at the
+/// first error, in which case some of the messages may have already been sent.
+pub async fn send_chunked<R>(bot: &R, chat_id: ChatId, text: &str) -> Result<Vec<Message>, R::Err>
+where
+    R: Requester,
+{
+    let mut messages = Vec::new();
+    for chunk in split(text, MAX_MESSAGE_LENGTH) {
+        messages.push(bot.send_message(chat_id, chunk).send().await?);
+    }
+
+    Ok(messages)
+}
+
+/// Returns the length in bytes of the longest prefix of `text` that is at most
+/// `max_len` UTF-16 code units long, or `None` if the whole `text` fits.
+fn fitting_prefix_len(text: &str, max_len: usize) -> Option<usize> {
+    let mut len = 0;
+    for (pos, c) in text.char_indices() {
+        len += c.len_utf16();
+        if len > max_len {
+            return Some(pos);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16_len(s: &str) -> usize {
+        s.encode_utf16().count()
+    }
+
+    #[test]
+    fn split_short() {
+        assert_eq!(split("", 10), Vec::<&str>::new());
+        assert_eq!(split("hello", 10), ["hello"]);
+        assert_eq!(split("hello", 5), ["hello"]);
+    }
+
+    #[test]
+    fn split_at_separators() {
+        assert_eq!(split("aaa bbb\nccc ddd", 12), ["aaa bbb", "ccc ddd"]);
+        assert_eq!(split("aaa bbb ccc ddd", 12), ["aaa bbb ccc", "ddd"]);
+        assert_eq!(split("aaaaaaaa bb", 6), ["aaaaaa", "aa bb"]);
+        // Whitespace-only chunks are skipped.
+        assert_eq!(split("aaa\n\n\n\n\n\n\nbbb", 4), ["aaa", "bbb"]);
+    }
+
+    #[test]
+    fn split_long_text() {
+        let text = "lorem ipsum dolor sit amet ".repeat(400);
+        assert!(text.len() > 10_000);
+
+        let chunks = split(&text, MAX_MESSAGE_LENGTH);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|chunk| utf16_len(chunk) <= MAX_MESSAGE_LENGTH));
+        // Nothing but the separators is lost.
+        assert_eq!(chunks.join(" "), text);
+    }
+
+    #[test]
+    fn split_multi_unit_chars() {
+        // "👋" is 2 UTF-16 code units and 4 bytes long.
+        let text = "👋".repeat(5000);
+
+        let chunks = split(&text, MAX_MESSAGE_LENGTH);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0], "👋".repeat(2048));
+        assert_eq!(chunks[1], "👋".repeat(2048));
+        assert_eq!(chunks[2], "👋".repeat(904));
+
+        assert_eq!(split("a👋b", 2), ["a", "👋", "b"]);
+    }
+}
@@ -53,7 +53,7 @@ pub fn strike(s: &str) -> String {
 
 /// Builds an inline link with an anchor.
 ///
-/// Escapes `)` and ``` characters inside the link url.
+/// Escapes `\`, `)` and ``` characters inside the link url.
 pub fn link(url: &str, text: &str) -> String {
     format!("[{}]({})", text, escape_link_url(url))
 }
@@ -87,9 +87,26 @@ pub fn code_inline(s: &str) -> String {
 /// Escapes the string to be shown "as is" within the Telegram [Markdown
 /// v2][spec] message style.
 ///
-/// [spec]: https://core.telegram.org/bots/api#html-style
+/// Use it to interpolate arbitrary (e.g., user-provided) text into a message.
+/// All the reserved characters are escaped, as well as `\` itself, so that it
+/// cannot escape the character following it.
+///
+/// Note that URLs of inline links and the contents of code entities have their
+/// own rules, see [`escape_link_url`] and [`escape_code`].
+///
+/// ## Example
+///
+/// ```
+/// use teloxide::utils::markdown;
+///
+/// let name = "Mr_Smith (admin)!";
+/// assert_eq!(format!("Hello, {}\\!", markdown::escape(name)), r"Hello, Mr\_Smith \(admin\)\!\!");
+/// ```
+///
+/// [spec]: https://core.telegram.org/bots/api#markdownv2-style
 pub fn escape(s: &str) -> String {
-    s.replace('_', r"\_")
+    s.replace('\\', r"\\")
+        .replace('_', r"\_")
         .replace('*', r"\*")
         .replace('[', r"\[")
         .replace(']', r"\]")
@@ -110,9 +127,9 @@ pub fn escape(s: &str) -> String {
 }
 
 /// Escapes all markdown special characters specific for the inline link URL
-/// (``` and `)`).
+/// (`\`, ``` and `)`).
 pub fn escape_link_url(s: &str) -> String {
-    s.replace('`', r"\`").replace(')', r"\)")
+    s.replace('\\', r"\\").replace('`', r"\`").replace(')', r"\)")
 }
 
 /// Escapes all markdown special characters specific for the code block (``` and
@@ -128,6 +145,161 @@ pub fn user_mention_or_link(user: &User) -> String {
     }
 }
 
+/// A builder of a whole message in the [Markdown V2][spec] style.
+///
+/// Unlike the functions of this module, it escapes all the passed strings, so
+/// that arbitrary (e.g., user-provided) text cannot break the markup. Nested
+/// entities are built by the `*_with` methods from an inner builder.
+///
+/// ## Example
+///
+/// ```
+/// use teloxide::utils::markdown;
+///
+/// let name = "Mr_Smith (admin)";
+/// let message = markdown::Builder::new()
+///     .text("Hello, ")
+///     .bold(name)
+///     .text("! Read ")
+///     .link("https://example.com/rules_(en)", "the rules")
+///     .text(".")
+///     .build();
+/// assert_eq!(
+///     message,
+///     r"Hello, *Mr\_Smith \(admin\)*\! Read [the rules](https://example.com/rules_(en\))\."
+/// );
+/// ```
+///
+/// [spec]: https://core.telegram.org/bots/api#markdownv2-style
+#[derive(Clone, Debug, Default)]
+pub struct Builder {
+    message: String,
+}
+
+impl Builder {
+    /// Constructs a builder of an empty message.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends plain text.
+    #[must_use]
+    pub fn text(self, s: &str) -> Self {
+        self.push(&escape(s))
+    }
+
+    /// Appends bold text.
+    #[must_use]
+    pub fn bold(self, s: &str) -> Self {
+        self.push(&bold(&escape(s)))
+    }
+
+    /// Appends italic text.
+    #[must_use]
+    pub fn italic(self, s: &str) -> Self {
+        self.push(&italic(&escape(s)))
+    }
+
+    /// Appends underlined text.
+    #[must_use]
+    pub fn underline(self, s: &str) -> Self {
+        self.push(&underline(&escape(s)))
+    }
+
+    /// Appends strikethrough text.
+    #[must_use]
+    pub fn strike(self, s: &str) -> Self {
+        self.push(&strike(&escape(s)))
+    }
+
+    /// Appends a bold entity with the contents built by `f`.
+    #[must_use]
+    pub fn bold_with<F>(self, f: F) -> Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        let inner = f(Self::new());
+        self.push(&bold(&inner.message))
+    }
+
+    /// Appends an italic entity with the contents built by `f`.
+    #[must_use]
+    pub fn italic_with<F>(self, f: F) -> Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        let inner = f(Self::new());
+        self.push(&italic(&inner.message))
+    }
+
+    /// Appends an underline entity with the contents built by `f`.
+    #[must_use]
+    pub fn underline_with<F>(self, f: F) -> Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        let inner = f(Self::new());
+        self.push(&underline(&inner.message))
+    }
+
+    /// Appends a strikethrough entity with the contents built by `f`.
+    #[must_use]
+    pub fn strike_with<F>(self, f: F) -> Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        let inner = f(Self::new());
+        self.push(&strike(&inner.message))
+    }
+
+    /// Appends an inline link with `text` as an anchor.
+    ///
+    /// `text` is escaped as plain text, and `url` as an inline link URL.
+    #[must_use]
+    pub fn link(self, url: &str, text: &str) -> Self {
+        self.push(&link(url, &escape(text)))
+    }
+
+    /// Appends an inline user mention with `text` as an anchor.
+    #[must_use]
+    pub fn user_mention(self, user_id: i64, text: &str) -> Self {
+        self.link(&format!("tg://user?id={}", user_id), text)
+    }
+
+    /// Appends an inline code.
+    ///
+    /// Only ``` and `\` characters are escaped, as required inside code
+    /// entities.
+    #[must_use]
+    pub fn code(self, s: &str) -> Self {
+        self.push(&code_inline(s))
+    }
+
+    /// Appends a code block.
+    #[must_use]
+    pub fn code_block(self, code: &str) -> Self {
+        self.push(&code_block(code))
+    }
+
+    /// Appends a code block with a specific language syntax.
+    #[must_use]
+    pub fn code_block_with_lang(self, code: &str, lang: &str) -> Self {
+        self.push(&code_block_with_lang(code, lang))
+    }
+
+    /// Returns the built message.
+    #[must_use]
+    pub fn build(self) -> String {
+        self.message
+    }
+
+    fn push(mut self, s: &str) -> Self {
+        self.message.push_str(s);
+        self
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -208,8 +380,24 @@ mod tests {
         assert_eq!(escape("* foobar *"), r"\* foobar \*");
         assert_eq!(
             escape(r"_ * [ ] ( ) ~ \ ` > # + - = | { } . !"),
-            r"\_ \* \[ \] \( \) \~ \ \` \> \# \+ \- \= \| \{ \} \. \!",
+            r"\_ \* \[ \] \( \) \~ \\ \` \> \# \+ \- \= \| \{ \} \. \!",
         );
+        // An escaped backslash must not escape the following character.
+        assert_eq!(escape(r"\_"), r"\\\_");
+    }
+
+    #[test]
+    fn test_escape_every_char() {
+        const RESERVED: &str = r"_*[]()~`>#+-=|{}.!\";
+
+        for c in RESERVED.chars() {
+            assert_eq!(escape(&c.to_string()), format!("\\{}", c));
+        }
+
+        let others = (' '..='~').chain(['\n', 'ё', '👋']).filter(|&c| !RESERVED.contains(c));
+        for c in others {
+            assert_eq!(escape(&c.to_string()), c.to_string());
+        }
     }
 
     #[test]
@@ -222,7 +410,7 @@ mod tests {
             escape_link_url(r"https://en.wikipedia.org/wiki/`"),
             r"https://en.wikipedia.org/wiki/\`"
         );
-        assert_eq!(escape_link_url(r"_*[]()~`#+-=|{}.!\"), r"_*[](\)~\`#+-=|{}.!\");
+        assert_eq!(escape_link_url(r"_*[]()~`#+-=|{}.!\"), r"_*[](\)~\`#+-=|{}.!\\");
     }
 
     #[test]
@@ -255,4 +443,23 @@ mod tests {
             r#"[Name](tg://user/?id=123456789)"#
         )
     }
+
+    #[test]
+    fn test_builder() {
+        let message = Builder::new()
+            .text("2 * 2 = 4. ")
+            .bold("*bold*")
+            .text(" ")
+            .italic_with(|b| b.underline("both"))
+            .text(" ")
+            .code("let x = `a\\b`;")
+            .build();
+        assert_eq!(message, r"2 \* 2 \= 4\. *\*bold\** ___both_\r__ `let x = \`a\\b\`;`");
+
+        let message = Builder::new().link("https://example.com", "[link]").build();
+        assert_eq!(message, r"[\[link\]](https://example.com)");
+
+        let message = Builder::new().link(r"https://example.com/a\", "link").build();
+        assert_eq!(message, r"[link](https://example.com/a\\)");
+    }
 }
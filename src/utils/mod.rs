@@ -1,9 +1,13 @@
 //! Some useful utilities.
 
+pub mod chat_action;
 pub mod command;
 pub mod html;
+pub mod long_text;
 pub mod markdown;
 pub(crate) mod shutdown_token;
+#[cfg(feature = "webapp")]
+pub mod webapp;
 
 pub use teloxide_core::net::client_from_env;
 
@@ -33,8 +33,12 @@ impl ShutdownToken {
     ///
     /// Returns an error if the dispatcher is idle at the moment.
     ///
-    /// If you don't need to wait for shutdown, the returned future can be
-    /// ignored.
+    /// The returned future resolves once the dispatcher has stopped, i.e.
+    /// after all the updates that were being processed are handled (or
+    /// aborted because of [`DispatcherBuilder::shutdown_timeout`]). If you
+    /// don't need to wait for shutdown, the returned future can be ignored.
+    ///
+    /// [`DispatcherBuilder::shutdown_timeout`]: crate::dispatching::DispatcherBuilder::shutdown_timeout
     pub fn shutdown(&self) -> Result<impl Future<Output = ()> + '_, IdleShutdownError> {
         match shutdown_inner(&self.dispatcher_state) {
             Ok(()) | Err(Ok(AlreadyShuttingDown)) => Ok(async move {
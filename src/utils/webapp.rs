@@ -0,0 +1,263 @@
+//! Validating data received from a [Web App].
+//!
+//! [Web App]: https://core.telegram.org/bots/webapps
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use thiserror::Error;
+
+use crate::types::UserId;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Data that a Web App receives from Telegram, validated by
+/// [`validate_init_data`].
+#[derive(Clone, Debug)]
+pub struct WebAppInitData {
+    /// A unique identifier of the Web App session, required for sending a
+    /// message via [`AnswerWebAppQuery`].
+    ///
+    /// [`AnswerWebAppQuery`]: https://core.telegram.org/bots/api#answerwebappquery
+    pub query_id: Option<String>,
+
+    /// The current user.
+    pub user: Option<WebAppUser>,
+
+    /// The chat partner of the current user, if the Web App was opened via
+    /// the attachment menu in a private chat.
+    pub receiver: Option<WebAppUser>,
+
+    /// The type of the chat from which the Web App was opened.
+    pub chat_type: Option<String>,
+
+    /// A global identifier of the chat from which the Web App was opened.
+    pub chat_instance: Option<String>,
+
+    /// The value of the `startattach` parameter passed via a link.
+    pub start_param: Option<String>,
+
+    /// When the form was opened.
+    pub auth_date: SystemTime,
+}
+
+/// A user of a Web App.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct WebAppUser {
+    /// A unique identifier of the user or bot.
+    pub id: UserId,
+
+    /// `true`, if the user is a bot.
+    #[serde(default)]
+    pub is_bot: bool,
+
+    /// The first name of the user or bot.
+    pub first_name: String,
+
+    /// The last name of the user or bot.
+    pub last_name: Option<String>,
+
+    /// The username of the user or bot.
+    pub username: Option<String>,
+
+    /// The [IETF language tag] of the user's language.
+    ///
+    /// [IETF language tag]: https://en.wikipedia.org/wiki/IETF_language_tag
+    pub language_code: Option<String>,
+
+    /// `true`, if the user is a Telegram Premium user.
+    #[serde(default)]
+    pub is_premium: bool,
+
+    /// A URL of the user's profile photo, in `.jpeg` or `.svg` format.
+    pub photo_url: Option<String>,
+}
+
+/// An error returned from [`validate_init_data`].
+#[derive(Debug, Error)]
+pub enum InitDataError {
+    /// The data has no `hash` field, so it can't be validated.
+    #[error("the `hash` field is missing")]
+    MissingHash,
+
+    /// The `hash` field is not a valid hex string or doesn't match the data,
+    /// i.e. the data is not signed by Telegram for the bot.
+    #[error("the hash doesn't match the data")]
+    InvalidHash,
+
+    /// The `auth_date` field is missing or is not a Unix timestamp.
+    #[error("the `auth_date` field is missing or invalid")]
+    InvalidAuthDate,
+
+    /// The data is older than the `max_age` passed to [`validate_init_data`].
+    #[error("the data is outdated ({0:?} old)")]
+    Outdated(Duration),
+
+    /// The `user` or `receiver` field (named by `field`) is not a valid JSON
+    /// of a [`WebAppUser`].
+    #[error("invalid `{field}` field: {source}")]
+    InvalidUser { field: &'static str, source: serde_json::Error },
+}
+
+/// Validates `init_data` received from a Web App.
+///
+/// `init_data` is the raw query string from `Telegram.WebApp.initData`, which
+/// a Web App has passed to your backend. It is checked to be signed by Telegram
+/// for the bot with `token`, as described in [the documentation], and to be
+/// not older than `max_age`, so that intercepted data cannot be reused
+/// forever.
+///
+/// Never trust `Telegram.WebApp.initDataUnsafe` (or anything else obtained on
+/// the client side) without this validation.
+///
+/// ## Example
+///
+/// ```
+/// use std::time::Duration;
+/// use teloxide::utils::webapp::{self, InitDataError};
+///
+/// let result = webapp::validate_init_data(
+///     "query_id=AAHdF6IQAAAAAN0XohDhrOrc&auth_date=1662771648&hash=0000",
+///     "123456:ABC-DEF1234ghIkl-zyx57W2v1u123ew11",
+///     Duration::from_secs(60 * 60),
+/// );
+/// assert!(matches!(result, Err(InitDataError::InvalidHash)));
+/// ```
+///
+/// [the documentation]: https://core.telegram.org/bots/webapps#validating-data-received-via-the-web-app
+pub fn validate_init_data(
+    init_data: &str,
+    token: &str,
+    max_age: Duration,
+) -> Result<WebAppInitData, InitDataError> {
+    let mut hash = None;
+    let mut fields = Vec::new();
+    for (key, value) in url::form_urlencoded::parse(init_data.as_bytes()) {
+        if key == "hash" {
+            hash = Some(value);
+        } else {
+            fields.push((key, value));
+        }
+    }
+    let hash = hash.ok_or(InitDataError::MissingHash)?;
+    let hash = decode_hex(&hash).ok_or(InitDataError::InvalidHash)?;
+
+    fields.sort();
+    let data_check_string = fields
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let secret_key = hmac_sha256(b"WebAppData", token.as_bytes());
+    let mut mac = HmacSha256::new_from_slice(&secret_key).expect("HMAC accepts keys of any size");
+    mac.update(data_check_string.as_bytes());
+    // Compares in constant time.
+    mac.verify_slice(&hash).map_err(|_| InitDataError::InvalidHash)?;
+
+    let field = |name: &str| {
+        fields.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone().into_owned())
+    };
+    let user = |name: &'static str| {
+        field(name)
+            .map(|user| serde_json::from_str(&user))
+            .transpose()
+            .map_err(|source| InitDataError::InvalidUser { field: name, source })
+    };
+
+    let auth_date = field("auth_date")
+        .and_then(|date| date.parse().ok())
+        .and_then(|secs| UNIX_EPOCH.checked_add(Duration::from_secs(secs)))
+        .ok_or(InitDataError::InvalidAuthDate)?;
+    let age = SystemTime::now().duration_since(auth_date).unwrap_or_default();
+    if age > max_age {
+        return Err(InitDataError::Outdated(age));
+    }
+
+    Ok(WebAppInitData {
+        query_id: field("query_id"),
+        user: user("user")?,
+        receiver: user("receiver")?,
+        chat_type: field("chat_type"),
+        chat_instance: field("chat_instance"),
+        start_param: field("start_param"),
+        auth_date,
+    })
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "123456:ABC-DEF1234ghIkl-zyx57W2v1u123ew11";
+    const INIT_DATA: &str = concat!(
+        "query_id=AAHdF6IQAAAAAN0XohDhrOrc",
+        "&user=%7B%22id%22%3A279058397%2C%22first_name%22%3A%22Vladislav%22%2C%22last_name%22",
+        "%3A%22Kibenko%22%2C%22username%22%3A%22vdkfrost%22%2C%22language_code%22%3A%22ru%22",
+        "%2C%22is_premium%22%3Atrue%7D",
+        "&auth_date=1662771648",
+        "&hash=7a789370203900df724df4b8b4945aaefb7ce5898c70be807cf76c2426ffec49",
+    );
+
+    const FOREVER: Duration = Duration::from_secs(u64::MAX);
+
+    #[test]
+    fn valid() {
+        let data = validate_init_data(INIT_DATA, TOKEN, FOREVER).unwrap();
+        assert_eq!(data.query_id.as_deref(), Some("AAHdF6IQAAAAAN0XohDhrOrc"));
+        assert_eq!(data.auth_date, UNIX_EPOCH + Duration::from_secs(1662771648));
+        assert_eq!(data.receiver, None);
+
+        let user = data.user.unwrap();
+        assert_eq!(user.id, UserId(279058397));
+        assert_eq!(user.first_name, "Vladislav");
+        assert_eq!(user.username.as_deref(), Some("vdkfrost"));
+        assert!(user.is_premium);
+    }
+
+    #[test]
+    fn forged() {
+        let wrong_token = "654321:ABC-DEF1234ghIkl-zyx57W2v1u123ew11";
+        assert!(matches!(
+            validate_init_data(INIT_DATA, wrong_token, FOREVER),
+            Err(InitDataError::InvalidHash)
+        ));
+
+        let tampered = INIT_DATA.replace("1662771648", "1662771649");
+        assert!(matches!(
+            validate_init_data(&tampered, TOKEN, FOREVER),
+            Err(InitDataError::InvalidHash)
+        ));
+
+        let unsigned = INIT_DATA.split("&hash=").next().unwrap();
+        assert!(matches!(
+            validate_init_data(unsigned, TOKEN, FOREVER),
+            Err(InitDataError::MissingHash)
+        ));
+    }
+
+    #[test]
+    fn outdated() {
+        let max_age = Duration::from_secs(60 * 60);
+        assert!(matches!(
+            validate_init_data(INIT_DATA, TOKEN, max_age),
+            Err(InitDataError::Outdated(age)) if age > max_age
+        ));
+    }
+}
@@ -87,6 +87,30 @@ fn global_attributes() {
     assert_eq!(DefaultCommands::descriptions().to_string(), "Bot commands\n\n/start\n!help");
 }
 
+#[test]
+#[cfg(feature = "macros")]
+fn mixed_prefixes() {
+    #[derive(BotCommands, Debug, PartialEq)]
+    #[command(prefix = "!", rename = "lowercase")]
+    enum DefaultCommands {
+        Ban,
+        #[command(prefix = ".")]
+        Kick,
+        #[command(prefix = "/")]
+        Help,
+    }
+
+    assert_eq!(DefaultCommands::Ban, DefaultCommands::parse("!ban", "").unwrap());
+    assert_eq!(DefaultCommands::Kick, DefaultCommands::parse(".kick", "").unwrap());
+    assert_eq!(DefaultCommands::Help, DefaultCommands::parse("/help", "").unwrap());
+
+    // A command is only recognised with its own prefix.
+    assert!(DefaultCommands::parse("/ban", "").is_err());
+    assert!(DefaultCommands::parse("!kick", "").is_err());
+
+    assert_eq!(DefaultCommands::descriptions().to_string(), "!ban\n.kick\n/help");
+}
+
 #[test]
 #[cfg(feature = "macros")]
 fn parse_command_with_bot_name() {
@@ -234,3 +258,130 @@ fn rename_rules() {
         DefaultCommands::descriptions().to_string()
     );
 }
+
+#[test]
+#[cfg(feature = "macros")]
+fn parse_with_bot_name_ignores_case() {
+    #[derive(BotCommands, Debug, PartialEq)]
+    #[command(rename = "lowercase")]
+    enum DefaultCommands {
+        Start(String),
+        Help,
+    }
+
+    assert_eq!(
+        DefaultCommands::Start("arg".to_owned()),
+        DefaultCommands::parse_with_bot_name("/start@mynamebot arg", "MyNameBot").unwrap()
+    );
+    assert_eq!(
+        DefaultCommands::Help,
+        DefaultCommands::parse_with_bot_name("/help@MYNAMEBOT", "MyNameBot").unwrap()
+    );
+    assert_eq!(
+        DefaultCommands::Help,
+        DefaultCommands::parse_with_bot_name("/help", "MyNameBot").unwrap()
+    );
+
+    // The exact comparison is still available via `parse`.
+    assert!(DefaultCommands::parse("/help@mynamebot", "MyNameBot").is_err());
+    assert!(matches!(
+        DefaultCommands::parse_with_bot_name("/help@OtherBot", "MyNameBot"),
+        Err(ParseError::WrongBotName(name)) if name == "OtherBot"
+    ));
+}
+
+#[test]
+#[cfg(feature = "macros")]
+fn describe_parse_error() {
+    use teloxide::utils::command::describe_parse_error;
+
+    #[derive(BotCommands, Debug, PartialEq)]
+    #[command(rename = "lowercase")]
+    enum DefaultCommands {
+        #[command(description = "start the bot")]
+        Start,
+        #[command(description = "roll a die")]
+        Roll(u8),
+    }
+
+    let describe = |text| {
+        let err = DefaultCommands::parse(text, "MyNameBot").unwrap_err();
+        describe_parse_error::<DefaultCommands>(text, &err)
+    };
+
+    assert_eq!(describe("/strat").unwrap(), "Unknown command /strat. Did you mean /start?");
+    assert_eq!(
+        describe("/settings@MyNameBot").unwrap(),
+        "Unknown command /settings.\n\n/start — start the bot\n/roll — roll a die"
+    );
+    assert!(describe("/roll six").unwrap().ends_with("\n\n/roll — roll a die"));
+    assert_eq!(describe("hello"), None);
+    assert_eq!(describe("/start@OtherBot"), None);
+}
+
+#[tokio::test]
+#[cfg(feature = "macros")]
+async fn filter_command_with_error() {
+    use std::{ops::ControlFlow, sync::Arc};
+    use teloxide::{prelude::*, types::Me};
+
+    #[derive(BotCommands, Clone, Debug, PartialEq)]
+    #[command(rename = "lowercase")]
+    enum DefaultCommands {
+        Start,
+        Num(u8),
+    }
+
+    let message = |mut message: serde_json::Value| -> Update {
+        message["message_id"] = 1.into();
+        message["date"] = 0.into();
+        message["chat"] = serde_json::json!({ "id": 1, "type": "private", "first_name": "User" });
+        message["from"] = serde_json::json!({ "id": 1, "is_bot": false, "first_name": "User" });
+        serde_json::from_value(serde_json::json!({ "update_id": 1, "message": message })).unwrap()
+    };
+    let me: Me = serde_json::from_value(serde_json::json!({
+        "id": 42,
+        "is_bot": true,
+        "first_name": "Bot",
+        "username": "MyNameBot",
+        "can_join_groups": true,
+        "can_read_all_group_messages": false,
+        "supports_inline_queries": false,
+    }))
+    .unwrap();
+
+    let handler = Update::filter_message().filter_command_with_error::<DefaultCommands>().endpoint(
+        |res: Result<DefaultCommands, Arc<ParseError>>| async move {
+            res.map_err(|err| err.to_string())
+        },
+    );
+    let dispatch = |update| handler.dispatch(dptree::deps![update, me.clone()]);
+
+    assert!(matches!(
+        dispatch(message(serde_json::json!({ "text": "/num@MyNameBot 42" }))).await,
+        ControlFlow::Break(Ok(DefaultCommands::Num(42)))
+    ));
+    assert!(matches!(
+        dispatch(message(serde_json::json!({ "text": "/num many" }))).await,
+        ControlFlow::Break(Err(_))
+    ));
+    assert!(matches!(
+        dispatch(message(serde_json::json!({ "text": "/unknown" }))).await,
+        ControlFlow::Break(Err(_))
+    ));
+    // Text messages which are not commands and commands for other bots are
+    // skipped.
+    assert!(matches!(
+        dispatch(message(serde_json::json!({ "text": "hello" }))).await,
+        ControlFlow::Continue(_)
+    ));
+    assert!(matches!(
+        dispatch(message(serde_json::json!({ "text": "/start@OtherBot" }))).await,
+        ControlFlow::Continue(_)
+    ));
+    // Messages without text are skipped.
+    assert!(matches!(
+        dispatch(message(serde_json::json!({ "dice": { "emoji": "🎲", "value": 6 } }))).await,
+        ControlFlow::Continue(_)
+    ));
+}
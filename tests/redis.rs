@@ -3,7 +3,9 @@ use std::{
     sync::Arc,
 };
 use teloxide::{
-    dispatching::dialogue::{RedisStorage, RedisStorageError, Serializer, Storage},
+    dispatching::dialogue::{
+        ListableStorage, RedisStorage, RedisStorageError, Serializer, Storage,
+    },
     types::ChatId,
 };
 
@@ -43,6 +45,61 @@ async fn test_redis_cbor() {
     test_redis(storage).await;
 }
 
+#[tokio::test]
+#[cfg_attr(not(CI_REDIS), ignore)]
+async fn test_redis_key_prefix() {
+    let first = RedisStorage::open_with_key_prefix(
+        "redis://127.0.0.1:7777",
+        "first_bot",
+        teloxide::dispatching::dialogue::serializer::Json,
+    )
+    .await
+    .unwrap();
+    let second = RedisStorage::open_with_key_prefix(
+        "redis://127.0.0.1:7777",
+        "second_bot",
+        teloxide::dispatching::dialogue::serializer::Json,
+    )
+    .await
+    .unwrap();
+
+    Arc::clone(&second).update_dialogue(ChatId(1), "XYZ".to_owned()).await.unwrap();
+    let chat_ids = ListableStorage::<Dialogue>::get_all_chat_ids(Arc::clone(&second)).await;
+    assert_eq!(chat_ids.unwrap(), [ChatId(1)]);
+    let chat_ids = ListableStorage::<Dialogue>::get_all_chat_ids(Arc::clone(&first)).await;
+    assert!(chat_ids.unwrap().is_empty());
+    test_redis(first).await;
+
+    // Dialogues of the second bot are not affected by the first one.
+    assert_eq!(Arc::clone(&second).get_dialogue(ChatId(1)).await.unwrap(), Some("XYZ".to_owned()));
+    Storage::<Dialogue>::remove_dialogue(Arc::clone(&second), ChatId(1)).await.unwrap();
+}
+
+#[tokio::test]
+#[cfg_attr(not(CI_REDIS), ignore)]
+async fn test_redis_chat_ids_without_prefix() {
+    // A separate database, so that dialogues of other tests don't interfere.
+    const URL: &str = "redis://127.0.0.1:7777/1";
+
+    let mut conn = redis::Client::open(URL).unwrap().get_async_connection().await.unwrap();
+    redis::AsyncCommands::set::<_, _, ()>(&mut conn, "not_a_dialogue", "value").await.unwrap();
+
+    let storage =
+        RedisStorage::open(URL, teloxide::dispatching::dialogue::serializer::Json).await.unwrap();
+    Arc::clone(&storage).update_dialogue(ChatId(1), "ABC".to_owned()).await.unwrap();
+    Arc::clone(&storage).update_dialogue(ChatId(-100), "DEF".to_owned()).await.unwrap();
+
+    // Keys that are not chat IDs are skipped.
+    let mut chat_ids =
+        ListableStorage::<Dialogue>::get_all_chat_ids(Arc::clone(&storage)).await.unwrap();
+    chat_ids.sort_by_key(|&ChatId(id)| id);
+    assert_eq!(chat_ids, [ChatId(-100), ChatId(1)]);
+
+    Storage::<Dialogue>::remove_dialogue(Arc::clone(&storage), ChatId(1)).await.unwrap();
+    Storage::<Dialogue>::remove_dialogue(Arc::clone(&storage), ChatId(-100)).await.unwrap();
+    redis::AsyncCommands::del::<_, ()>(&mut conn, "not_a_dialogue").await.unwrap();
+}
+
 type Dialogue = String;
 
 macro_rules! test_dialogues {
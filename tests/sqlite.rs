@@ -4,7 +4,9 @@ use std::{
     sync::Arc,
 };
 use teloxide::{
-    dispatching::dialogue::{Serializer, SqliteStorage, SqliteStorageError, Storage},
+    dispatching::dialogue::{
+        ListableStorage, Serializer, SqliteStorage, SqliteStorageError, Storage,
+    },
     types::ChatId,
 };
 
@@ -75,6 +77,11 @@ where
         Some("GHI".to_owned())
     );
 
+    let mut chat_ids =
+        ListableStorage::<Dialogue>::get_all_chat_ids(Arc::clone(&storage)).await.unwrap();
+    chat_ids.sort_by_key(|chat_id| chat_id.0);
+    assert_eq!(chat_ids, [ChatId(1), ChatId(11), ChatId(256)]);
+
     Arc::clone(&storage).remove_dialogue(ChatId(1)).await.unwrap();
     Arc::clone(&storage).remove_dialogue(ChatId(11)).await.unwrap();
     Arc::clone(&storage).remove_dialogue(ChatId(256)).await.unwrap();